@@ -0,0 +1,21 @@
+use advent::amplifier::{run_all, run_all_parallel};
+use advent::util::default_workers;
+use criterion::{Criterion, criterion_group, criterion_main};
+use futures::executor::block_on;
+use std::fs;
+
+fn amplifier_benchmark(c: &mut Criterion) {
+    let code = fs::read_to_string("input/day7.txt")
+        .expect("Unable to read input file")
+        .replace('\n', "");
+    let workers = default_workers();
+    let mut group = c.benchmark_group("day7");
+    group.bench_function("sequential", |b| b.iter(|| block_on(run_all(code.clone()))));
+    group.bench_function("parallel", |b| {
+        b.iter(|| run_all_parallel(code.clone(), workers))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, amplifier_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,61 @@
+use advent::orbit::{self, OrbitMap, OrbitTree};
+use criterion::{Criterion, criterion_group, criterion_main};
+use std::fs;
+
+fn orbit_benchmark(c: &mut Criterion) {
+    let code = fs::read_to_string("input/day6.txt").expect("Unable to read input file");
+    let orbits = orbit::parse_orbits(&code).expect("Invalid orbit map");
+    let mut group = c.benchmark_group("day6");
+    group.bench_function("checksum/dijkstra", |b| {
+        b.iter(|| OrbitMap::from_orbits(&orbits).distance("COM", None))
+    });
+    group.bench_function("checksum/tree", |b| {
+        b.iter(|| OrbitTree::from_orbits(&orbits).checksum())
+    });
+    group.bench_function("transfers/dijkstra", |b| {
+        b.iter(|| OrbitMap::from_orbits(&orbits).distance("SAN", Some("YOU")) - 2)
+    });
+    group.bench_function("transfers/tree", |b| {
+        b.iter(|| OrbitTree::from_orbits(&orbits).transfers("YOU", "SAN"))
+    });
+    group.finish();
+
+    // queries on a prepared map, Dijkstra visiting the whole map for each of them
+    let code = orbit::generate(100_000, 1);
+    let orbits = orbit::parse_orbits(&code).expect("Invalid orbit map");
+    let queries: Vec<(String, String)> = (1..=10)
+        .map(|i| {
+            (
+                format!("B{}", i * 9_973),
+                format!("B{}", 100_000 - i * 7_919),
+            )
+        })
+        .collect();
+    let graph = OrbitMap::from_orbits(&orbits);
+    let tree = OrbitTree::from_orbits(&orbits);
+    let mut group = c.benchmark_group("generated");
+    group.sample_size(10);
+    group.bench_function("10 queries/dijkstra", |b| {
+        b.iter(|| {
+            queries
+                .iter()
+                .map(|(a, z)| graph.distance(a, Some(z)))
+                .sum::<usize>()
+        })
+    });
+    group.bench_function("10 queries/tree", |b| {
+        b.iter(|| {
+            queries
+                .iter()
+                .map(|(a, z)| tree.distance(a, z).unwrap())
+                .sum::<usize>()
+        })
+    });
+    group.bench_function("preprocessing/tree", |b| {
+        b.iter(|| OrbitTree::from_orbits(&orbits))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, orbit_benchmark);
+criterion_main!(benches);
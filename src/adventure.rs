@@ -0,0 +1,569 @@
+//! Text adventure of Advent of Code 2019 Day 25 (https://adventofcode.com/2019/day/25)
+//!
+//! The ASCII program describes the room the droid is in, its doors and the items lying there,
+//! then waits for a command: a direction, `take <item>`, `drop <item>` or `inv`. The security
+//! checkpoint only lets the droid through the pressure-sensitive floor when it carries the
+//! right weight.
+
+use crate::ascii::{self, Reply};
+use crate::intcode::{Intcode, State};
+use log::*;
+use petgraph::algo::astar;
+use petgraph::dot::Dot;
+use petgraph::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+/// Room holding the weight check
+pub const CHECKPOINT: &str = "Security Checkpoint";
+
+/// Items which cannot be undone by restoring a snapshot: one never gives the control back,
+/// the other one prevents the droid from moving
+pub const DANGEROUS: [&str; 2] = ["infinite loop", "giant electromagnet"];
+
+/// Something playing the game: a snapshot of it is a clone
+pub trait Console: Clone {
+    /// Sends `input` and returns the text shown until the game waits for the next command
+    fn send(&mut self, input: &str) -> Reply;
+}
+
+impl Console for Intcode {
+    fn send(&mut self, input: &str) -> Reply {
+        ascii::send(self, input)
+    }
+}
+
+fn opposite(direction: &str) -> Option<&'static str> {
+    match direction {
+        "north" => Some("south"),
+        "south" => Some("north"),
+        "east" => Some("west"),
+        "west" => Some("east"),
+        _ => None,
+    }
+}
+
+fn is_direction(command: &str) -> bool {
+    matches!(command, "north" | "south" | "east" | "west")
+}
+
+/// Returns the entries of the `- ` list following the `header` line
+fn list(text: &str, header: &str) -> Vec<String> {
+    text.lines()
+        .skip_while(|l| *l != header)
+        .skip(1)
+        .take_while(|l| l.starts_with("- "))
+        .map(|l| l[2..].to_string())
+        .collect()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Room {
+    pub name: String,
+    pub doors: Vec<String>,
+    pub items: Vec<String>,
+}
+
+impl Room {
+    /// Returns the last room described in `text`
+    pub fn parse(text: &str) -> Option<Self> {
+        let start = text
+            .match_indices("== ")
+            .map(|(i, _)| i)
+            .filter(|i| text[*i..].lines().next().unwrap_or("").ends_with(" =="))
+            .last()?;
+        let text = &text[start..];
+        let title = text.lines().next()?;
+        Some(Self {
+            name: title[3..title.len() - 3].to_string(),
+            doors: list(text, "Doors here lead:"),
+            items: list(text, "Items here:"),
+        })
+    }
+}
+
+/// Rooms seen so far, linked by the doors the droid went through
+#[derive(Clone, Debug, Default)]
+pub struct Map {
+    graph: Graph<String, String>,
+    rooms: HashMap<String, NodeIndex>,
+}
+
+impl Map {
+    fn node(&mut self, name: &str) -> NodeIndex {
+        let graph = &mut self.graph;
+        *self
+            .rooms
+            .entry(name.to_string())
+            .or_insert_with(|| graph.add_node(name.to_string()))
+    }
+
+    /// Records that going `direction` from `from` leads to `to`, and back, or returns an error
+    /// when `direction` is not a compass point
+    pub fn connect(&mut self, from: &str, direction: &str, to: &str) -> Result<(), String> {
+        let back =
+            opposite(direction).ok_or_else(|| format!("Invalid direction: {}", direction))?;
+        let (a, b) = (self.node(from), self.node(to));
+        if self.graph.find_edge(a, b).is_none() {
+            self.graph.add_edge(a, b, direction.to_string());
+            self.graph.add_edge(b, a, back.to_string());
+        }
+        Ok(())
+    }
+
+    /// Number of rooms seen so far
+    pub fn len(&self) -> usize {
+        self.rooms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rooms.is_empty()
+    }
+
+    /// Returns the directions to follow to go from room `from` to room `to`
+    pub fn path(&self, from: &str, to: &str) -> Option<Vec<String>> {
+        let (start, goal) = (*self.rooms.get(from)?, *self.rooms.get(to)?);
+        let (_, nodes) = astar(&self.graph, start, |n| n == goal, |_| 1, |_| 0)?;
+        Some(
+            nodes
+                .windows(2)
+                .map(|w| self.graph[self.graph.find_edge(w[0], w[1]).unwrap()].clone())
+                .collect(),
+        )
+    }
+
+    /// Returns the map in the dot format of graphviz
+    pub fn dot(&self) -> String {
+        format!("{}", Dot::new(&self.graph))
+    }
+}
+
+pub struct Game<C: Console> {
+    console: C,
+    halted: bool,
+    room: Option<Room>,
+    history: Vec<String>,
+    snapshots: HashMap<String, (C, Option<Room>)>,
+    map: Map,
+    checkpoint: Option<String>,
+}
+
+impl<C: Console> Game<C> {
+    pub fn new(console: C) -> Self {
+        Self {
+            console,
+            halted: false,
+            room: None,
+            history: Vec::new(),
+            snapshots: HashMap::new(),
+            map: Map::default(),
+            checkpoint: None,
+        }
+    }
+
+    fn send(&mut self, input: &str) -> String {
+        let reply = self.console.send(input);
+        self.halted = reply.state == State::Halted;
+        if let Some(room) = Room::parse(&reply.text) {
+            self.map.node(&room.name);
+            self.room = Some(room);
+        }
+        reply.text
+    }
+
+    /// Runs the game until it asks for the first command
+    pub fn start(&mut self) -> String {
+        self.send("")
+    }
+
+    /// Sends a command, moves through doors being recorded on the map
+    pub fn command(&mut self, command: &str) -> String {
+        let command = command.trim();
+        self.history.push(command.to_string());
+        let from = self.room.as_ref().map(|r| r.name.clone());
+        let text = self.send(&format!("{}\n", command));
+        if let (Some(from), Some(to)) = (from, &self.room) {
+            if is_direction(command) && from != to.name {
+                if let Err(e) = self.map.connect(&from, command, &to.name) {
+                    warn!("{}", e);
+                }
+            }
+        }
+        text
+    }
+
+    pub fn halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Room the droid is in, once described
+    pub fn room(&self) -> Option<&Room> {
+        self.room.as_ref()
+    }
+
+    /// Commands sent so far, oldest first
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    pub fn map(&self) -> &Map {
+        &self.map
+    }
+
+    /// Saves the state of the game under `name`
+    pub fn save(&mut self, name: &str) {
+        self.snapshots
+            .insert(name.to_string(), (self.console.clone(), self.room.clone()));
+    }
+
+    /// Restores the state of the game saved under `name`, returning false if there is none
+    pub fn load(&mut self, name: &str) -> bool {
+        match self.snapshots.get(name) {
+            Some((console, room)) => {
+                self.console = console.clone();
+                self.room = room.clone();
+                self.halted = false;
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Items carried by the droid
+    pub fn inventory(&mut self) -> Vec<String> {
+        list(&self.command("inv"), "Items in your inventory:")
+    }
+
+    /// Takes `item` unless it is known to be dangerous or ends the game, returning true if the
+    /// droid carries it
+    fn take_safely(&mut self, item: &str) -> bool {
+        if DANGEROUS.contains(&item) {
+            debug!("leaving the {}", item);
+            return false;
+        }
+        self.save("before taking");
+        let text = self.command(&format!("take {}", item));
+        if self.halted || !text.contains("You take") {
+            warn!("taking the {} is fatal", item);
+            self.load("before taking");
+            return false;
+        }
+        true
+    }
+
+    /// Visits every room reachable without crossing the pressure-sensitive floor, taking every
+    /// safe item on the way, and comes back to the starting room
+    pub fn explore(&mut self) {
+        if self.room.is_none() {
+            self.start();
+        }
+        let mut visited = HashSet::new();
+        self.visit(&mut visited, None);
+    }
+
+    fn visit(&mut self, visited: &mut HashSet<String>, back: Option<&str>) {
+        let room = match &self.room {
+            Some(room) => room.clone(),
+            None => return,
+        };
+        visited.insert(room.name.clone());
+        for item in &room.items {
+            self.take_safely(item);
+        }
+        if room.name == CHECKPOINT {
+            self.checkpoint = room
+                .doors
+                .iter()
+                .find(|d| Some(d.as_str()) != back)
+                .cloned();
+            return;
+        }
+        for door in &room.doors {
+            if Some(door.as_str()) == back {
+                continue;
+            }
+            let return_door = match opposite(door) {
+                Some(d) => d,
+                None => {
+                    warn!("ignoring the door {}, which leads nowhere known", door);
+                    continue;
+                },
+            };
+            self.command(door);
+            let name = self.room.as_ref().map(|r| r.name.clone());
+            if name.filter(|n| !visited.contains(n)).is_some() {
+                self.visit(visited, Some(return_door));
+            }
+            self.command(return_door);
+        }
+    }
+
+    /// Goes to the checkpoint and tries the combinations of the carried items until the floor
+    /// lets the droid through, each try only taking or dropping one item, in Gray-code order
+    ///
+    /// Returns the text shown when the droid passes.
+    pub fn crack(&mut self) -> Result<String, String> {
+        let door = self
+            .checkpoint
+            .clone()
+            .ok_or("The checkpoint was not found")?;
+        let from = self
+            .room
+            .as_ref()
+            .ok_or("The game did not start")?
+            .name
+            .clone();
+        let path = self
+            .map
+            .path(&from, CHECKPOINT)
+            .ok_or("No path to the checkpoint")?;
+        for direction in path {
+            self.command(&direction);
+        }
+        let items = self.inventory();
+        let mut dropped = 0u64;
+        for i in 0..1u64 << items.len() {
+            let gray = i ^ (i >> 1);
+            let changed = gray ^ dropped;
+            if changed != 0 {
+                let item = &items[changed.trailing_zeros() as usize];
+                if gray & changed != 0 {
+                    self.command(&format!("drop {}", item));
+                } else {
+                    self.command(&format!("take {}", item));
+                }
+                dropped = gray;
+            }
+            let text = self.command(&door);
+            if !text.contains("Alert!") {
+                info!("passed the checkpoint after {} tries", i + 1);
+                return Ok(text);
+            }
+        }
+        Err(format!(
+            "No combination of {:?} passes the checkpoint",
+            items
+        ))
+    }
+}
+
+/// Returns the password given when passing the checkpoint
+pub fn password(text: &str) -> Option<String> {
+    text.split_whitespace()
+        .find(|w| w.len() > 1 && w.chars().all(|c| c.is_ascii_digit()))
+        .map(|w| w.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Small ship: four rooms in a loop, then the checkpoint
+    #[derive(Clone)]
+    struct FakeShip {
+        room: &'static str,
+        floor: HashMap<&'static str, Vec<String>>,
+        inventory: Vec<String>,
+    }
+
+    fn doors(room: &str) -> Vec<(&'static str, &'static str)> {
+        match room {
+            "Hull Breach" => vec![("north", "Kitchen"), ("east", "Hallway")],
+            "Kitchen" => vec![("south", "Hull Breach"), ("east", "Lab")],
+            "Lab" => vec![("west", "Kitchen"), ("south", "Hallway")],
+            "Hallway" => vec![
+                ("west", "Hull Breach"),
+                ("north", "Lab"),
+                ("east", CHECKPOINT),
+            ],
+            CHECKPOINT => vec![("west", "Hallway"), ("east", "Pressure-Sensitive Floor")],
+            _ => vec![],
+        }
+    }
+
+    fn weight(item: &str) -> u32 {
+        match item {
+            "mug" => 1,
+            "spool of cat6" => 2,
+            "tambourine" => 4,
+            _ => 100,
+        }
+    }
+
+    impl FakeShip {
+        fn new() -> Self {
+            let mut floor = HashMap::new();
+            floor.insert("Kitchen", vec!["mug".to_string()]);
+            floor.insert(
+                "Lab",
+                vec!["spool of cat6".to_string(), "molten lava".to_string()],
+            );
+            floor.insert(
+                "Hallway",
+                vec!["infinite loop".to_string(), "tambourine".to_string()],
+            );
+            Self {
+                room: "Hull Breach",
+                floor,
+                inventory: Vec::new(),
+            }
+        }
+
+        fn describe(&self, room: &str) -> String {
+            let mut text = format!(
+                "\n\n\n== {} ==\nA room of the ship.\n\nDoors here lead:\n",
+                room
+            );
+            for (door, _) in doors(room) {
+                text += &format!("- {}\n", door);
+            }
+            if let Some(items) = self.floor.get(room).filter(|i| !i.is_empty()) {
+                text += "\nItems here:\n";
+                for item in items {
+                    text += &format!("- {}\n", item);
+                }
+            }
+            text + "\nCommand?\n"
+        }
+    }
+
+    impl Console for FakeShip {
+        fn send(&mut self, input: &str) -> Reply {
+            let command = input.trim();
+            let mut state = State::WaitingForInput;
+            let text = if command.is_empty() {
+                self.describe(self.room)
+            } else if let Some(item) = command.strip_prefix("take ") {
+                assert_ne!(
+                    item, "infinite loop",
+                    "the game never gives the control back"
+                );
+                let floor = self.floor.entry(self.room).or_default();
+                floor.retain(|i| i != item);
+                self.inventory.push(item.to_string());
+                if item == "molten lava" {
+                    state = State::Halted;
+                    "\nYou take the molten lava.\n\nYou melt!\n".to_string()
+                } else {
+                    format!("\nYou take the {}.\n\nCommand?\n", item)
+                }
+            } else if let Some(item) = command.strip_prefix("drop ") {
+                self.inventory.retain(|i| i != item);
+                self.floor
+                    .entry(self.room)
+                    .or_default()
+                    .push(item.to_string());
+                format!("\nYou drop the {}.\n\nCommand?\n", item)
+            } else if command == "inv" {
+                let mut text = "\nItems in your inventory:\n".to_string();
+                for item in &self.inventory {
+                    text += &format!("- {}\n", item);
+                }
+                text + "\nCommand?\n"
+            } else {
+                match doors(self.room).iter().find(|(d, _)| *d == command) {
+                    Some((_, "Pressure-Sensitive Floor")) => {
+                        let total = self.inventory.iter().map(|i| weight(i)).sum::<u32>();
+                        if total == 5 {
+                            state = State::Halted;
+                            "\n\n\n== Pressure-Sensitive Floor ==\nAnalysis complete! You \
+                             may proceed.\nYou should be able to get in by typing 2424 on the \
+                             keypad at the main airlock.\n"
+                                .to_string()
+                        } else {
+                            "\n\n\n== Pressure-Sensitive Floor ==\nAlert! Droids on this \
+                             ship are heavier than the detected value!\n"
+                                .to_string()
+                                + &self.describe(CHECKPOINT)
+                        }
+                    },
+                    Some((_, room)) => {
+                        self.room = room;
+                        self.describe(room)
+                    },
+                    None => "\nYou can't go that way.\n\nCommand?\n".to_string(),
+                }
+            };
+            Reply {
+                text,
+                value: None,
+                state,
+            }
+        }
+    }
+
+    #[test]
+    fn test_room() {
+        let room = Room::parse(&FakeShip::new().describe("Lab")).unwrap();
+        assert_eq!(room.name, "Lab");
+        assert_eq!(room.doors, vec!["west", "south"]);
+        assert_eq!(room.items, vec!["spool of cat6", "molten lava"]);
+        let text = "== Pressure-Sensitive Floor ==\nAlert!\n\n\n== Security Checkpoint ==\n\
+                    A room.\n\nDoors here lead:\n- west\n\nCommand?\n";
+        let room = Room::parse(text).unwrap();
+        assert_eq!(room.name, CHECKPOINT);
+        assert_eq!(room.items, Vec::<String>::new());
+        assert_eq!(Room::parse("You take the mug."), None);
+    }
+
+    #[test]
+    fn test_commands() {
+        let mut game = Game::new(FakeShip::new());
+        game.start();
+        assert_eq!(game.room().unwrap().name, "Hull Breach");
+        game.command("north");
+        game.save("kitchen");
+        assert!(game.command("take mug").contains("You take the mug."));
+        assert_eq!(game.inventory(), vec!["mug"]);
+        assert!(game.load("kitchen"));
+        assert!(!game.load("nowhere"));
+        assert_eq!(game.inventory(), Vec::<String>::new());
+        game.command("east");
+        game.command("south");
+        assert_eq!(game.room().unwrap().name, "Hallway");
+        assert_eq!(
+            game.history(),
+            ["north", "take mug", "inv", "inv", "east", "south"]
+        );
+        assert_eq!(game.map().len(), 4);
+        assert_eq!(
+            game.map().path("Hallway", "Kitchen"),
+            Some(vec!["north".to_string(), "west".to_string()])
+        );
+        assert!(game.map().dot().contains("Lab"));
+
+        let mut map = Map::default();
+        assert_eq!(map.connect("Hallway", "north", "Kitchen"), Ok(()));
+        assert_eq!(
+            map.connect("Hallway", "up", "Attic"),
+            Err("Invalid direction: up".to_string())
+        );
+        assert_eq!(map.path("Hallway", "Attic"), None);
+        assert_eq!(
+            map.path("Kitchen", "Hallway"),
+            Some(vec!["south".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_explore_and_crack() {
+        let mut game = Game::new(FakeShip::new());
+        game.explore();
+        assert_eq!(game.room().unwrap().name, "Hull Breach");
+        assert_eq!(game.map().len(), 5);
+        let mut items = game.inventory();
+        items.sort();
+        assert_eq!(items, vec!["mug", "spool of cat6", "tambourine"]);
+        let text = game.crack().unwrap();
+        assert!(game.halted());
+        assert_eq!(password(&text), Some("2424".to_string()));
+        // every try only takes or drops one item
+        let history = game.history();
+        let start = history.iter().rposition(|c| c == "inv").unwrap();
+        let tries = history[start..].iter().filter(|c| *c == "east").count();
+        let changes = history[start..]
+            .iter()
+            .filter(|c| c.starts_with("take ") || c.starts_with("drop "))
+            .count();
+        assert_eq!((tries, changes), (4, 3));
+    }
+}
@@ -0,0 +1,501 @@
+//! Amplifier chains for Advent of Code 2019 Day 7 (https://adventofcode.com/2019/day/7)
+//!
+//! Two ways to try every phase setting are available:
+//!   - `run_all` evaluates the permutations one after the other
+//!   - `run_all_parallel` spreads them on a pool of worker threads, each amplifier of a chain
+//!     talking to the next one through a channel
+
+use crate::coverage::Coverage;
+use crate::intcode::{Intcode, State};
+use futures::future::join_all;
+use itertools::Itertools;
+use log::*;
+use permutohedron::LexicalPermutation;
+use std::cell::Cell;
+use std::cmp::Reverse;
+use std::str;
+use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Returns every phase setting, in lexical order
+pub fn phase_permutations() -> Vec<Vec<usize>> {
+    let mut data = [0, 1, 2, 3, 4];
+    let mut permutations = Vec::new();
+
+    loop {
+        permutations.push(data.to_vec());
+        if !data.next_permutation() {
+            break;
+        }
+    }
+    permutations
+}
+
+pub async fn calculate(code: String, input: Vec<usize>) -> i64 {
+    input.iter().fold(0, |last, i| {
+        let mut p = Intcode::new(code.clone());
+        debug!("running p.run({}, {})", i, last);
+        let mut output = Vec::new();
+        let str_input = format!("{}\n{}\n", *i, last);
+        let buffer = str_input.as_bytes();
+        p.run(buffer, &mut output);
+        let output: i64 = str::from_utf8(&output)
+            .expect("unable to parse output")
+            .trim_end()
+            .parse()
+            .expect("unable to parse output");
+        debug!("output: {:?}", output);
+        output
+    })
+}
+
+pub async fn run_all(code: String) -> Vec<i64> {
+    let permutations = phase_permutations();
+    let futures = permutations
+        .iter()
+        .map(|p| calculate(code.clone(), p.to_vec()));
+    join_all(futures).await
+}
+
+/// How the amplifiers of a chain are wired
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Topology {
+    /// The last amplifier gives the final signal
+    Series,
+    /// The last amplifier loops back to the first one until every amplifier halts
+    Feedback,
+}
+
+impl FromStr for Topology {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "series" => Ok(Topology::Series),
+            "feedback" => Ok(Topology::Feedback),
+            _ => Err(format!(
+                "Invalid topology: {} (expected series or feedback)",
+                s
+            )),
+        }
+    }
+}
+
+/// Signal obtained with a given phase ordering, or why the chain failed to give one
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    pub phases: Vec<i64>,
+    pub signal: Result<i64, String>,
+}
+
+/// Parameters of the search for the best phase ordering
+#[derive(Debug, Clone)]
+pub struct Search {
+    /// Number of amplifiers in the chain
+    pub amplifiers: usize,
+    /// Phases to pick from, each of them used at most once per chain
+    pub phases: Vec<i64>,
+    /// Signal sent to the first amplifier
+    pub signal: i64,
+    pub topology: Topology,
+}
+
+impl Default for Search {
+    fn default() -> Self {
+        Self {
+            amplifiers: 5,
+            phases: vec![0, 1, 2, 3, 4],
+            signal: 0,
+            topology: Topology::Series,
+        }
+    }
+}
+
+impl Search {
+    /// Returns every phase ordering, following the order of the phase set
+    pub fn permutations(&self) -> Vec<Vec<i64>> {
+        self.phases
+            .iter()
+            .cloned()
+            .permutations(self.amplifiers)
+            .collect()
+    }
+
+    /// Evaluates every phase ordering on `workers` threads
+    ///
+    /// Outcomes are returned in the same order as `permutations`. With a single worker, the
+    /// orderings are evaluated on the calling thread.
+    pub fn run(&self, code: &str, workers: usize) -> Vec<Outcome> {
+        let permutations = self.permutations();
+        if workers <= 1 {
+            return permutations
+                .into_iter()
+                .map(|phases| self.outcome(code, phases))
+                .collect();
+        }
+        let count = permutations.len();
+        let (job_tx, job_rx) = mpsc::channel::<(usize, Vec<i64>)>();
+        let job_rx = Arc::new(Mutex::new(job_rx));
+        let (result_tx, result_rx) = mpsc::channel();
+        for (i, p) in permutations.into_iter().enumerate() {
+            job_tx.send((i, p)).expect("Unable to queue permutation");
+        }
+        drop(job_tx);
+
+        let handles: Vec<_> = (0..workers)
+            .map(|w| {
+                let code = code.to_string();
+                let search = self.clone();
+                let job_rx = Arc::clone(&job_rx);
+                let result_tx = result_tx.clone();
+                thread::spawn(move || {
+                    loop {
+                        let job = job_rx.lock().expect("Job queue poisoned").recv();
+                        match job {
+                            Ok((i, phases)) => {
+                                debug!("worker {} runs {:?}", w, phases);
+                                let outcome = search.outcome(&code, phases);
+                                result_tx.send((i, outcome)).expect("Unable to send result");
+                            },
+                            Err(_) => break,
+                        }
+                    }
+                })
+            })
+            .collect();
+        drop(result_tx);
+
+        let mut results = vec![None; count];
+        for (i, outcome) in result_rx {
+            results[i] = Some(outcome);
+        }
+        for handle in handles {
+            handle.join().expect("Worker thread panicked");
+        }
+        results
+            .into_iter()
+            .map(|o| o.expect("Missing outcome"))
+            .collect()
+    }
+
+    /// Evaluates every phase ordering and sorts them from the strongest signal to the weakest,
+    /// the failed orderings coming last
+    pub fn ranked(&self, code: &str, workers: usize) -> Vec<Outcome> {
+        let mut outcomes = self.run(code, workers);
+        outcomes.sort_by_key(|o| (o.signal.is_err(), Reverse(o.signal.clone().ok())));
+        outcomes
+    }
+
+    /// Returns the phase ordering giving the strongest signal, if any
+    pub fn best(&self, code: &str, workers: usize) -> Option<Outcome> {
+        self.ranked(code, workers)
+            .into_iter()
+            .next()
+            .filter(|o| o.signal.is_ok())
+    }
+
+    /// Evaluates every phase ordering on the calling thread and merges the coverage of every
+    /// amplifier
+    pub fn coverage(&self, code: &str) -> Coverage {
+        let mut coverage = Coverage::default();
+        for phases in self.permutations() {
+            let mut programs = vec![Intcode::new(code.to_string()); phases.len()];
+            programs.iter_mut().for_each(Intcode::enable_coverage);
+            if let Err(e) = run_amplifiers(&mut programs, &phases, self.signal, self.topology) {
+                warn!("{} with phases {:?}", e, phases);
+            }
+            for program in &programs {
+                coverage.merge(program.coverage().expect("Coverage not recorded"));
+            }
+        }
+        coverage
+    }
+
+    fn outcome(&self, code: &str, phases: Vec<i64>) -> Outcome {
+        let signal = run_chain(code, &phases, self.signal, self.topology);
+        if let Err(e) = &signal {
+            debug!("{} with phases {:?}", e, phases);
+        }
+        Outcome { phases, signal }
+    }
+}
+
+/// Runs one amplifier chain, the amplifiers being connected by channels
+///
+/// Amplifier `n` reads its phase then the signals sent by amplifier `n - 1`, the first one
+/// receives `signal` followed, in a feedback loop, by the signals of the last one. An amplifier
+/// waiting for a signal hands over to the next one, so the whole chain runs on the calling
+/// thread. Returns the last signal emitted by the last amplifier, or an error when every
+/// amplifier still running waits for a signal that will never come or when an amplifier uses a
+/// negative address.
+pub fn run_chain(
+    code: &str,
+    phases: &[i64],
+    signal: i64,
+    topology: Topology,
+) -> Result<i64, String> {
+    let mut programs = vec![Intcode::new(code.to_string()); phases.len()];
+    run_amplifiers(&mut programs, phases, signal, topology)
+}
+
+/// Same as `run_chain`, on computers prepared by the caller, one per phase
+pub fn run_amplifiers(
+    programs: &mut [Intcode],
+    phases: &[i64],
+    signal: i64,
+    topology: Topology,
+) -> Result<i64, String> {
+    let (first_tx, mut previous_rx): (Sender<i64>, Receiver<i64>) = mpsc::channel();
+    let mut amplifiers = Vec::new();
+    for (i, phase) in phases.iter().enumerate() {
+        let (mut tx, rx) = mpsc::channel();
+        if topology == Topology::Feedback && i == phases.len() - 1 {
+            tx = first_tx.clone();
+        }
+        let mut phase = Some(*phase);
+        let input = previous_rx;
+        amplifiers.push((
+            move || phase.take().or_else(|| input.try_recv().ok()),
+            tx,
+            State::WaitingForInput,
+        ));
+        previous_rx = rx;
+    }
+    first_tx
+        .send(signal)
+        .expect("Unable to send initial signal");
+    let last = amplifiers.len().saturating_sub(1);
+    let mut result = signal;
+    while amplifiers
+        .iter()
+        .any(|(_, _, state)| *state != State::Halted)
+    {
+        // a round in which no signal moved leaves every amplifier waiting, forever
+        let progress = Cell::new(false);
+        let chain = programs.iter_mut().zip(amplifiers.iter_mut()).enumerate();
+        for (i, (program, (input, output, state))) in chain {
+            if *state != State::Halted {
+                *state = program.try_run_with(
+                    || {
+                        let value = input();
+                        if value.is_some() {
+                            progress.set(true);
+                        }
+                        value
+                    },
+                    |value| {
+                        progress.set(true);
+                        if i == last {
+                            result = value;
+                        }
+                        output.send(value).expect("Amplifier output closed")
+                    },
+                )?;
+            }
+        }
+        if !progress.get()
+            && amplifiers
+                .iter()
+                .any(|(_, _, state)| *state != State::Halted)
+        {
+            return Err("amplifiers deadlocked waiting for input".to_string());
+        }
+    }
+    Ok(result)
+}
+
+/// Same as `run_all`, but the permutations are spread on `workers` threads
+///
+/// Results are returned in the same order as `phase_permutations`, a chain which deadlocked
+/// giving an error.
+pub fn run_all_parallel(code: String, workers: usize) -> Vec<Result<i64, String>> {
+    Search::default()
+        .run(&code, workers)
+        .into_iter()
+        .map(|o| o.signal)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+
+    const EXAMPLES: [(&str, i64); 3] = [
+        ("3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0", 43210),
+        (
+            "3,23,3,24,1002,24,10,24,1002,23,-1,23,101,5,23,23,1,24,23,23,4,23,99,0,0",
+            54321,
+        ),
+        (
+            "3,31,3,32,1002,32,10,32,1001,31,-2,31,1007,31,0,33,1002,33,7,33,1,33,31,31,1,32,31,\
+             31,4,31,99,0,0,0",
+            65210,
+        ),
+    ];
+
+    const FEEDBACK_EXAMPLES: [(&str, i64); 2] = [
+        (
+            "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5",
+            139629729,
+        ),
+        (
+            "3,52,1001,52,-5,52,3,53,1,52,56,54,1007,54,5,55,1005,55,26,1001,54,-5,54,1105,1,12,1,\
+             53,54,53,1008,54,0,55,1001,55,1,55,2,53,55,53,4,53,1001,56,-1,56,1005,56,6,99,0,0,0,\
+             0,10",
+            18216,
+        ),
+    ];
+
+    #[test]
+    fn test_run_chain() {
+        let series = Topology::Series;
+        assert_eq!(
+            run_chain(EXAMPLES[0].0, &[4, 3, 2, 1, 0], 0, series),
+            Ok(43210)
+        );
+        assert_eq!(
+            run_chain(EXAMPLES[1].0, &[0, 1, 2, 3, 4], 0, series),
+            Ok(54321)
+        );
+        assert_eq!(
+            run_chain(EXAMPLES[2].0, &[1, 0, 4, 3, 2], 0, series),
+            Ok(65210)
+        );
+        assert_eq!(run_chain(EXAMPLES[0].0, &[], 7, series), Ok(7));
+    }
+
+    #[test]
+    fn test_run_chain_feedback() {
+        let feedback = Topology::Feedback;
+        let code = FEEDBACK_EXAMPLES[0].0;
+        assert_eq!(
+            run_chain(code, &[9, 8, 7, 6, 5], 0, feedback),
+            Ok(139629729)
+        );
+        let code = FEEDBACK_EXAMPLES[1].0;
+        assert_eq!(run_chain(code, &[9, 7, 8, 5, 6], 0, feedback), Ok(18216));
+    }
+
+    #[test]
+    fn test_run_chain_deadlock() {
+        // without the loop back, the feedback amplifiers wait for a second signal forever
+        let series = Topology::Series;
+        for (code, _) in FEEDBACK_EXAMPLES.iter() {
+            assert_eq!(
+                run_chain(code, &[9, 8, 7, 6, 5], 0, series),
+                Err("amplifiers deadlocked waiting for input".to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn test_search_best() {
+        let best = Search::default()
+            .best(EXAMPLES[0].0, 2)
+            .expect("No outcome");
+        assert_eq!(best.phases, vec![4, 3, 2, 1, 0]);
+        assert_eq!(best.signal, Ok(43210));
+
+        let search = Search {
+            phases: vec![5, 6, 7, 8, 9],
+            topology: Topology::Feedback,
+            ..Default::default()
+        };
+        for (code, signal) in FEEDBACK_EXAMPLES.iter() {
+            assert_eq!(search.best(code, 1).map(|o| o.signal), Some(Ok(*signal)));
+        }
+    }
+
+    #[test]
+    fn test_search_parameters() {
+        // A single amplifier computing signal * 10 + phase
+        let search = Search {
+            amplifiers: 1,
+            phases: vec![0, 4, 2],
+            signal: 3,
+            ..Default::default()
+        };
+        assert_eq!(search.permutations(), vec![vec![0], vec![4], vec![2]]);
+        let ranked = search.ranked(EXAMPLES[0].0, 1);
+        assert_eq!(
+            ranked,
+            vec![
+                Outcome {
+                    phases: vec![4],
+                    signal: Ok(34)
+                },
+                Outcome {
+                    phases: vec![2],
+                    signal: Ok(32)
+                },
+                Outcome {
+                    phases: vec![0],
+                    signal: Ok(30)
+                },
+            ]
+        );
+
+        let search = Search {
+            amplifiers: 6,
+            ..Default::default()
+        };
+        assert_eq!(search.best(EXAMPLES[0].0, 1), None);
+    }
+
+    #[test]
+    fn test_search_deadlock() {
+        // feedback programs wired in series deadlock whatever the phases
+        let search = Search {
+            phases: vec![5, 6, 7, 8, 9],
+            ..Default::default()
+        };
+        for workers in &[1, 4] {
+            let ranked = search.ranked(FEEDBACK_EXAMPLES[0].0, *workers);
+            assert_eq!(ranked.len(), 120);
+            assert!(ranked.iter().all(|o| o.signal.is_err()));
+            assert_eq!(search.best(FEEDBACK_EXAMPLES[0].0, *workers), None);
+        }
+
+        // a single amplifier looping back to itself keeps the signal moving until it halts
+        let search = Search {
+            amplifiers: 1,
+            phases: vec![5, 9],
+            topology: Topology::Feedback,
+            ..Default::default()
+        };
+        let ranked = search.ranked(FEEDBACK_EXAMPLES[0].0, 2);
+        assert!(ranked.iter().all(|o| o.signal.is_ok()));
+    }
+
+    #[test]
+    fn test_search_coverage() {
+        let coverage = Search::default().coverage(EXAMPLES[0].0);
+        assert_eq!(coverage.runs(), 600);
+        assert_eq!(coverage.executed(0), 600);
+        assert_eq!(coverage.executed(14), 600);
+        assert_eq!(coverage.writes(15), 1200);
+    }
+
+    #[test]
+    fn test_topology_from_str() {
+        assert_eq!("series".parse(), Ok(Topology::Series));
+        assert_eq!("feedback".parse(), Ok(Topology::Feedback));
+        assert!("ring".parse::<Topology>().is_err());
+    }
+
+    #[test]
+    fn test_parallel_matches_sequential() {
+        for (code, max) in EXAMPLES.iter() {
+            let sequential = block_on(run_all(code.to_string()));
+            for workers in &[1, 3, 8] {
+                let parallel = run_all_parallel(code.to_string(), *workers);
+                let parallel: Vec<i64> = parallel.into_iter().map(Result::unwrap).collect();
+                assert_eq!(parallel, sequential);
+            }
+            assert_eq!(sequential.iter().max(), Some(max));
+        }
+    }
+}
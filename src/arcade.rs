@@ -0,0 +1,311 @@
+//! Arcade cabinet of Advent of Code 2019 Day 13 (https://adventofcode.com/2019/day/13)
+//!
+//! The Intcode program draws the screen with `(x, y, tile)` triples, `(-1, 0, score)` updating
+//! the score display, and reads the position of the joystick when it needs it.
+
+use crate::grid::{self, Point};
+use crate::intcode::{Intcode, State};
+use log::*;
+use std::collections::HashMap;
+use std::io::BufRead;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tile {
+    Empty,
+    Wall,
+    Block,
+    Paddle,
+    Ball,
+}
+
+impl Tile {
+    fn from_code(code: i64) -> Self {
+        match code {
+            0 => Tile::Empty,
+            1 => Tile::Wall,
+            2 => Tile::Block,
+            3 => Tile::Paddle,
+            4 => Tile::Ball,
+            _ => panic!("Invalid tile: {}", code),
+        }
+    }
+
+    fn draw(self) -> char {
+        match self {
+            Tile::Empty => ' ',
+            Tile::Wall => '#',
+            Tile::Block => '=',
+            Tile::Paddle => '_',
+            Tile::Ball => 'o',
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Joystick {
+    Left,
+    Neutral,
+    Right,
+}
+
+impl Joystick {
+    fn code(self) -> i64 {
+        match self {
+            Joystick::Left => -1,
+            Joystick::Neutral => 0,
+            Joystick::Right => 1,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Screen {
+    tiles: HashMap<Point, Tile>,
+    score: i64,
+}
+
+impl Screen {
+    /// Applies one `(x, y, value)` triple sent by the program
+    pub fn update(&mut self, x: i64, y: i64, value: i64) {
+        if x == -1 && y == 0 {
+            self.score = value;
+        } else {
+            self.tiles.insert(Point::new(x, y), Tile::from_code(value));
+        }
+    }
+
+    pub fn score(&self) -> i64 {
+        self.score
+    }
+
+    /// Number of block tiles on the screen
+    pub fn blocks(&self) -> usize {
+        self.tiles.values().filter(|t| **t == Tile::Block).count()
+    }
+
+    fn find(&self, tile: Tile) -> Option<Point> {
+        self.tiles
+            .iter()
+            .find(|(_, t)| **t == tile)
+            .map(|(p, _)| *p)
+    }
+
+    pub fn ball(&self) -> Option<Point> {
+        self.find(Tile::Ball)
+    }
+
+    pub fn paddle(&self) -> Option<Point> {
+        self.find(Tile::Paddle)
+    }
+
+    /// Renders the screen followed by the score display
+    pub fn render(&self) -> String {
+        format!(
+            "{}Score: {}\n",
+            grid::render(&self.tiles, ' ', |t| t.draw()),
+            self.score
+        )
+    }
+}
+
+/// Something moving the joystick
+pub trait Player {
+    /// Returns the position of the joystick, or `None` when the player leaves the game
+    fn joystick(&mut self, screen: &Screen) -> Option<Joystick>;
+}
+
+/// Keeps the paddle under the ball
+pub struct Autopilot;
+
+impl Player for Autopilot {
+    fn joystick(&mut self, screen: &Screen) -> Option<Joystick> {
+        Some(match (screen.ball(), screen.paddle()) {
+            (Some(ball), Some(paddle)) if ball.x < paddle.x => Joystick::Left,
+            (Some(ball), Some(paddle)) if ball.x > paddle.x => Joystick::Right,
+            _ => Joystick::Neutral,
+        })
+    }
+}
+
+/// Reads the joystick from a line of text: `a` or `h` for left, `d` or `l` for right,
+/// anything else leaving it neutral, the end of the input leaving the game
+pub struct Keyboard<R: BufRead>(pub R);
+
+impl<R: BufRead> Player for Keyboard<R> {
+    fn joystick(&mut self, _: &Screen) -> Option<Joystick> {
+        let mut line = String::new();
+        let read = self
+            .0
+            .read_line(&mut line)
+            .expect("Unable to read joystick");
+        if read == 0 {
+            return None;
+        }
+        Some(match line.trim() {
+            "a" | "h" => Joystick::Left,
+            "d" | "l" => Joystick::Right,
+            _ => Joystick::Neutral,
+        })
+    }
+}
+
+pub struct Cabinet {
+    program: Intcode,
+    screen: Screen,
+}
+
+impl Cabinet {
+    pub fn new(program: Intcode) -> Self {
+        Self {
+            program,
+            screen: Screen::default(),
+        }
+    }
+
+    /// Sets the cabinet to play for free
+    pub fn insert_quarters(&mut self) {
+        self.program.write(0, 2);
+    }
+
+    pub fn screen(&self) -> &Screen {
+        &self.screen
+    }
+
+    /// Runs the game until the program halts or the player leaves
+    ///
+    /// `frame` is called with the screen every time the program waits for the joystick, and
+    /// once more when it halts. Returns `State::WaitingForInput` when the player left before the
+    /// end of the game.
+    pub fn play<P, F>(&mut self, player: &mut P, mut frame: F) -> State
+    where
+        P: Player,
+        F: FnMut(&Screen),
+    {
+        let mut outputs = Vec::new();
+        let mut joystick = None;
+        loop {
+            let state = self
+                .program
+                .run_with(|| joystick.take(), |v| outputs.push(v));
+            for triple in outputs.chunks_exact(3) {
+                self.screen.update(triple[0], triple[1], triple[2]);
+            }
+            let pending = outputs.len() % 3;
+            outputs.drain(..outputs.len() - pending);
+            frame(&self.screen);
+            if state == State::Halted {
+                return state;
+            }
+            let position = match player.joystick(&self.screen) {
+                Some(position) => position,
+                None => return state,
+            };
+            debug!("joystick: {:?}", position);
+            joystick = Some(position.code());
+        }
+    }
+}
+
+/// Score and remaining blocks when the game is over
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Outcome {
+    pub score: i64,
+    pub blocks: usize,
+}
+
+/// Plays the game for free on autopilot, without rendering anything
+pub fn headless(program: Intcode) -> Outcome {
+    let mut cabinet = Cabinet::new(program);
+    cabinet.insert_quarters();
+    cabinet.play(&mut Autopilot, |_| ());
+    Outcome {
+        score: cabinet.screen().score(),
+        blocks: cabinet.screen().blocks(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Draws a ball, a block and a paddle on one row, then shows the joystick as score
+    const GAME: &str = "1,0,0,0,104,1,104,0,104,4,104,2,104,0,104,2,104,3,104,0,104,3,3,100,\
+                        104,-1,104,0,4,100,99";
+
+    #[test]
+    fn test_screen() {
+        let mut screen = Screen::default();
+        screen.update(1, 2, 3);
+        screen.update(6, 5, 4);
+        screen.update(0, 0, 2);
+        screen.update(-1, 0, 12);
+        assert_eq!(screen.blocks(), 1);
+        assert_eq!(screen.paddle(), Some(Point::new(1, 2)));
+        assert_eq!(screen.ball(), Some(Point::new(6, 5)));
+        assert_eq!(screen.score(), 12);
+        screen.update(0, 0, 0);
+        assert_eq!(screen.blocks(), 0);
+    }
+
+    #[test]
+    fn test_render() {
+        let mut screen = Screen::default();
+        for (x, y, t) in &[(0, 0, 1), (1, 0, 2), (2, 0, 1), (1, 1, 4), (1, 2, 3)] {
+            screen.update(*x, *y, *t);
+        }
+        assert_eq!(screen.render(), "#=#\n o \n _ \nScore: 0\n");
+    }
+
+    #[test]
+    fn test_autopilot() {
+        let mut screen = Screen::default();
+        screen.update(4, 4, 4);
+        screen.update(4, 5, 3);
+        assert_eq!(Autopilot.joystick(&screen), Some(Joystick::Neutral));
+        screen.update(4, 4, 0);
+        screen.update(6, 3, 4);
+        assert_eq!(Autopilot.joystick(&screen), Some(Joystick::Right));
+        screen.update(6, 3, 0);
+        screen.update(2, 3, 4);
+        assert_eq!(Autopilot.joystick(&screen), Some(Joystick::Left));
+    }
+
+    #[test]
+    fn test_keyboard() {
+        let mut keyboard = Keyboard(&b"a\nl\n\n"[..]);
+        let screen = Screen::default();
+        assert_eq!(keyboard.joystick(&screen), Some(Joystick::Left));
+        assert_eq!(keyboard.joystick(&screen), Some(Joystick::Right));
+        assert_eq!(keyboard.joystick(&screen), Some(Joystick::Neutral));
+        assert_eq!(keyboard.joystick(&screen), None);
+    }
+
+    #[test]
+    fn test_headless() {
+        assert_eq!(
+            headless(Intcode::new(GAME.to_string())),
+            Outcome {
+                score: -1,
+                blocks: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_frames() {
+        let mut cabinet = Cabinet::new(Intcode::new(GAME.to_string()));
+        let mut frames = Vec::new();
+        let state = cabinet.play(&mut Keyboard(&b"d\n"[..]), |s| frames.push(s.render()));
+        assert_eq!(state, State::Halted);
+        assert_eq!(frames, vec!["o=_\nScore: 0\n", "o=_\nScore: 1\n"]);
+    }
+
+    #[test]
+    fn test_player_leaves() {
+        let mut cabinet = Cabinet::new(Intcode::new(GAME.to_string()));
+        let mut frames = Vec::new();
+        let state = cabinet.play(&mut Keyboard(&b""[..]), |s| frames.push(s.render()));
+        assert_eq!(state, State::WaitingForInput);
+        assert_eq!(frames, vec!["o=_\nScore: 0\n"]);
+    }
+}
@@ -0,0 +1,49 @@
+//! ASCII interface of the Intcode programs talking with text
+//!
+//! Inputs are sent one character code at a time, and outputs are character codes, except for a
+//! value out of the ASCII range that some programs send to report a result.
+
+use crate::intcode::{Intcode, State};
+
+/// What a program sent until it halted or needed more input
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reply {
+    pub text: String,
+    pub value: Option<i64>,
+    pub state: State,
+}
+
+/// Sends `input` to `program` and runs it until it halts or waits for more input
+pub fn send(program: &mut Intcode, input: &str) -> Reply {
+    let mut input = input.bytes();
+    let mut text = String::new();
+    let mut value = None;
+    let state = program.run_with(
+        || input.next().map(i64::from),
+        |v| match v {
+            0..=127 => text.push(v as u8 as char),
+            _ => value = Some(v),
+        },
+    );
+    Reply { text, value, state }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_send() {
+        let mut echo = Intcode::new("3,100,4,100,1105,1,0".to_string());
+        let reply = send(&mut echo, "hi\n");
+        assert_eq!(reply.text, "hi\n");
+        assert_eq!(reply.value, None);
+        assert_eq!(reply.state, State::WaitingForInput);
+
+        let mut report = Intcode::new("104,35,104,10,104,1000,99".to_string());
+        let reply = send(&mut report, "");
+        assert_eq!(reply.text, "#\n");
+        assert_eq!(reply.value, Some(1000));
+        assert_eq!(reply.state, State::Halted);
+    }
+}
@@ -0,0 +1,267 @@
+//! Tractor beam scanner of Advent of Code 2019 Day 19 (https://adventofcode.com/2019/day/19)
+//!
+//! The Intcode drone program reads the `x` and `y` coordinates of a position, sends back 1 if
+//! the beam pulls the drone there, 0 otherwise, and halts: every probe needs a fresh copy of the
+//! program.
+
+use crate::grid::{self, Point};
+use crate::intcode::Intcode;
+use log::*;
+use std::collections::HashMap;
+
+/// How far past the right edge of the last row holding beam cells the left edge of the next row
+/// is searched, before considering the row empty
+///
+/// Close to the emitter the beam is narrower than a cell, so some rows do not hold any.
+const SPREAD: i64 = 10;
+
+/// Number of rows, per unit of the size of the square, the beam may go on without widening
+/// before the search of a square fitting in it gives up
+const STALL: i64 = 10;
+
+pub struct Scanner {
+    program: Intcode,
+    probes: usize,
+}
+
+/// Edges of the beam on one row, if the row holds beam cells
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Row {
+    pub y: i64,
+    pub edges: Option<(i64, i64)>,
+}
+
+impl Scanner {
+    pub fn new(program: Intcode) -> Self {
+        Self { program, probes: 0 }
+    }
+
+    /// Number of times the drone program ran so far
+    pub fn probes(&self) -> usize {
+        self.probes
+    }
+
+    /// Returns true if the beam pulls the drone at `(x, y)`
+    pub fn probe(&mut self, x: i64, y: i64) -> bool {
+        self.probes += 1;
+        let mut drone = self.program.clone();
+        let mut inputs = vec![x, y].into_iter();
+        let mut output = None;
+        drone.run_with(|| inputs.next(), |v| output = Some(v));
+        match output {
+            Some(0) => false,
+            Some(1) => true,
+            o => panic!("Invalid drone reply: {:?}", o),
+        }
+    }
+
+    /// Probes every position of the `width` by `height` area closest to the emitter
+    pub fn scan(&mut self, width: i64, height: i64) -> HashMap<Point, bool> {
+        let mut cells = HashMap::new();
+        for y in 0..height {
+            for x in 0..width {
+                cells.insert(Point::new(x, y), self.probe(x, y));
+            }
+        }
+        cells
+    }
+
+    /// Returns the rows of the beam from the emitter onwards, only probing around their edges
+    ///
+    /// The rows end once more than `SPREAD` rows in a row are empty: the beam is lost.
+    pub fn rows(&mut self) -> Rows<'_> {
+        Rows {
+            scanner: self,
+            y: 0,
+            left: 0,
+            right: None,
+            empty: 0,
+        }
+    }
+
+    /// Number of positions pulled by the beam in the `size` by `size` area closest to the
+    /// emitter
+    pub fn affected(&mut self, size: i64) -> i64 {
+        self.rows()
+            .take(size as usize)
+            .filter_map(|r| r.edges)
+            .filter(|(left, _)| *left < size)
+            .map(|(left, right)| right.min(size - 1) - left + 1)
+            .sum()
+    }
+
+    /// Returns the top left corner of the `size` by `size` square closest to the emitter which
+    /// fits entirely in the beam
+    ///
+    /// Returns `None` for an empty square, or when the beam is lost or stops widening before
+    /// such a square fits in it.
+    pub fn closest_square(&mut self, size: i64) -> Option<Point> {
+        if size < 1 {
+            return None;
+        }
+        let mut rights = HashMap::new();
+        // width of the widest row so far and the row where the beam reached it
+        let mut widest = (0, 0);
+        for row in self.rows() {
+            let (left, right) = match row.edges {
+                Some(edges) => edges,
+                None => continue,
+            };
+            if right - left + 1 > widest.0 {
+                widest = (right - left + 1, row.y);
+            } else if row.y - widest.1 > STALL * size {
+                debug!(
+                    "beam stuck at a width of {} since row {}",
+                    widest.0, widest.1
+                );
+                return None;
+            }
+            rights.insert(row.y, right);
+            let top = row.y - size + 1;
+            if let Some(top_right) = rights.get(&top) {
+                if *top_right >= left + size - 1 {
+                    return Some(Point::new(left, top));
+                }
+            }
+        }
+        debug!("beam lost");
+        None
+    }
+}
+
+/// Iterator over the rows of the beam, following its edges
+pub struct Rows<'a> {
+    scanner: &'a mut Scanner,
+    y: i64,
+    left: i64,
+    right: Option<i64>,
+    empty: i64,
+}
+
+impl<'a> Iterator for Rows<'a> {
+    type Item = Row;
+
+    fn next(&mut self) -> Option<Row> {
+        if self.empty > SPREAD {
+            return None;
+        }
+        let y = self.y;
+        self.y += 1;
+        let limit = match self.right {
+            Some(right) => right + SPREAD,
+            None => SPREAD * (y + 1),
+        };
+        let mut left = self.left;
+        while left <= limit && !self.scanner.probe(left, y) {
+            left += 1;
+        }
+        if left > limit {
+            debug!("row {} is empty", y);
+            self.empty += 1;
+            return Some(Row { y, edges: None });
+        }
+        self.empty = 0;
+        // the beam widens as it goes away from the emitter: the right edge never moves back
+        let mut right = self.right.unwrap_or(left).max(left);
+        while self.scanner.probe(right + 1, y) {
+            right += 1;
+        }
+        self.left = left;
+        self.right = Some(right);
+        Some(Row {
+            y,
+            edges: Some((left, right)),
+        })
+    }
+}
+
+/// Renders the scanned area, `#` being the positions pulled by the beam
+pub fn render(cells: &HashMap<Point, bool>) -> String {
+    grid::render(cells, '.', |c| if *c { '#' } else { '.' })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Drone program of a beam where `1.2 x <= y <= 1.5 x`
+    const DRONE: &str = "3,100,3,101,1002,101,10,102,1002,100,12,103,7,102,103,104,\
+                         1002,100,3,105,1002,101,2,106,7,105,106,107,1,104,107,108,\
+                         1008,108,0,109,4,109,99";
+
+    fn pulled(x: i64, y: i64) -> bool {
+        10 * y >= 12 * x && 3 * x >= 2 * y
+    }
+
+    #[test]
+    fn test_probe() {
+        let mut scanner = Scanner::new(Intcode::new(DRONE.to_string()));
+        assert!(scanner.probe(0, 0));
+        assert!(!scanner.probe(1, 1));
+        assert!(scanner.probe(2, 3));
+        assert_eq!(scanner.probes(), 3);
+        let cells = scanner.scan(4, 5);
+        assert_eq!(scanner.probes(), 23);
+        assert_eq!(render(&cells), "#...\n....\n....\n..#.\n...#\n");
+    }
+
+    #[test]
+    fn test_rows() {
+        let mut scanner = Scanner::new(Intcode::new(DRONE.to_string()));
+        let rows = scanner.rows().take(200).collect::<Vec<_>>();
+        for row in rows {
+            let expected = (0..=row.y)
+                .filter(|x| pulled(*x, row.y))
+                .collect::<Vec<_>>();
+            let edges = expected.first().map(|l| (*l, *expected.last().unwrap()));
+            assert_eq!(row.edges, edges, "row {}", row.y);
+        }
+    }
+
+    #[test]
+    fn test_affected() {
+        let mut scanner = Scanner::new(Intcode::new(DRONE.to_string()));
+        let cells = scanner.scan(50, 50);
+        let expected = cells.values().filter(|c| **c).count() as i64;
+        let mut scanner = Scanner::new(Intcode::new(DRONE.to_string()));
+        assert_eq!(scanner.affected(50), expected);
+        assert!(scanner.probes() < 250, "{} probes", scanner.probes());
+    }
+
+    #[test]
+    fn test_closest_square() {
+        let size = 10;
+        let brute_force = (0..)
+            .flat_map(|y| (0..=y).map(move |x| Point::new(x, y)))
+            .find(|p| {
+                pulled(p.x, p.y) && pulled(p.x + size - 1, p.y) && pulled(p.x, p.y + size - 1)
+            })
+            .unwrap();
+        let mut scanner = Scanner::new(Intcode::new(DRONE.to_string()));
+        assert_eq!(scanner.closest_square(size), Some(brute_force));
+        // a few probes per row around the edges, instead of the whole rows
+        let rows = (brute_force.y + size) as usize;
+        assert!(scanner.probes() < 4 * rows, "{} probes", scanner.probes());
+    }
+
+    #[test]
+    fn test_no_square() {
+        let mut scanner = Scanner::new(Intcode::new(DRONE.to_string()));
+        assert_eq!(scanner.closest_square(0), None);
+        assert_eq!(scanner.probes(), 0);
+
+        // only the emitter position is pulled
+        let mut scanner = Scanner::new(Intcode::new(
+            "3,100,3,101,1,100,101,102,1008,102,0,103,4,103,99".to_string(),
+        ));
+        assert_eq!(scanner.rows().count() as i64, SPREAD + 2);
+        assert_eq!(scanner.closest_square(2), None);
+
+        // a beam along the diagonal, one position wide
+        let mut scanner = Scanner::new(Intcode::new(
+            "3,100,3,101,8,100,101,102,4,102,99".to_string(),
+        ));
+        assert_eq!(scanner.closest_square(1), Some(Point::new(0, 0)));
+        assert_eq!(scanner.closest_square(2), None);
+    }
+}
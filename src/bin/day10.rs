@@ -1,31 +1,95 @@
+use advent::util::{Random, default_workers};
 use log::*;
-use rust_decimal::prelude::*;
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
 use std::fs;
 use std::path::PathBuf;
+use std::str::FromStr;
+use std::thread;
 use structopt::StructOpt;
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "day10", about = "Advent of Code - Day 10")]
 struct Opt {
-    /// Activate debug mode
-    // short and long flags (-d, --debug) will be deduced from the field's name
+    /// Number of worker threads (defaults to the number of cores)
     #[structopt(short, long)]
-    debug: bool,
+    workers: Option<usize>,
+
+    /// Search the monitoring station on the current thread
+    #[structopt(short, long)]
+    sequential: bool,
+
+    /// Print the number of asteroids seen from every asteroid
+    #[structopt(short, long)]
+    visibility: bool,
+
+    /// Sweep of the laser: clockwise or counter-clockwise
+    #[structopt(short, long, default_value = "clockwise")]
+    rotation: Rotation,
+
+    /// First direction pointed by the laser, as dx,dy with the ordinates growing downwards
+    #[structopt(long, default_value = "0,-1", allow_hyphen_values = true)]
+    start: Direction,
+
+    /// Print every asteroid in the order the laser vaporizes them
+    #[structopt(short, long)]
+    order: bool,
+
+    /// Print the map coloured by the number of asteroids seen from every asteroid
+    #[structopt(short, long)]
+    colour: bool,
+
+    /// Draw the map coloured by visibility to this PPM file
+    #[structopt(long, parse(from_os_str))]
+    heatmap: Option<PathBuf>,
+
+    /// Pixels per cell of the PPM heatmap
+    #[structopt(long, default_value = "8")]
+    scale: usize,
+
+    /// Number the asteroids of the heatmaps in the order the laser vaporizes them
+    #[structopt(short, long)]
+    numbers: bool,
+
+    /// Generate a random map of this size, as WIDTHxHEIGHT, instead of reading one
+    #[structopt(short, long)]
+    generate: Option<Size>,
+
+    /// Probability of an asteroid in every cell of a generated map
+    #[structopt(long, default_value = "0.3")]
+    density: f64,
+
+    /// Seed of the generated map
+    #[structopt(long, default_value = "0")]
+    seed: u64,
+
+    /// Print the map, to save a generated one
+    #[structopt(short, long)]
+    print: bool,
 
     /// Input file
-    #[structopt(parse(from_os_str))]
-    input: PathBuf,
+    #[structopt(parse(from_os_str), required_unless = "generate")]
+    input: Option<PathBuf>,
 }
 
 #[derive(Clone)]
 struct AsteroidMap {
     asteroids: Vec<Asteroid>,
+    width: usize,
+    height: usize,
 }
 
 impl AsteroidMap {
     fn new(str_map: String) -> Self {
         Self {
+            width: str_map
+                .lines()
+                .map(|l| l.chars().count())
+                .max()
+                .unwrap_or(0),
+            height: str_map.lines().count(),
             asteroids: str_map
                 .lines()
                 .enumerate()
@@ -33,44 +97,216 @@ impl AsteroidMap {
                     l.chars()
                         .enumerate()
                         .filter(|(_, c)| *c == '#')
-                        .map(move |(j, _)| Asteroid {
-                            x: j,
-                            y: i,
-                            ..Default::default()
-                        })
+                        .map(move |(j, _)| Asteroid { x: j, y: i })
                 })
                 .collect(),
         }
     }
 
+    /// Returns a map where every cell holds an asteroid with the probability `density`, the
+    /// same seed giving the same map
+    fn generate(size: Size, density: f64, seed: u64) -> Self {
+        let mut random = Random::new(seed);
+        let mut asteroids = Vec::new();
+        for y in 0..size.height {
+            for x in 0..size.width {
+                if random.chance(density) {
+                    asteroids.push(Asteroid { x, y });
+                }
+            }
+        }
+        AsteroidMap {
+            asteroids,
+            width: size.width,
+            height: size.height,
+        }
+    }
+
     fn count(&self) -> usize {
         self.asteroids.len()
     }
 
-    fn vaporize(&mut self, asteroid: Asteroid) {
-        debug!("vaporize: {:?}", asteroid);
-        self.asteroids.retain(|x| *x != asteroid);
+    /// Asteroids vaporized by a laser set up on `station`, in the order they are destroyed
+    fn vaporization(
+        &self,
+        station: &Asteroid,
+        rotation: Rotation,
+        start: Direction,
+    ) -> Vaporization {
+        let mut lines: Vec<_> = station
+            .lines_of_sight(&self.asteroids)
+            .into_iter()
+            .map(|(d, line)| (d, VecDeque::from(line)))
+            .collect();
+        match rotation {
+            Rotation::Clockwise => lines.sort_by_key(|(d, _)| (*d < start, *d)),
+            Rotation::CounterClockwise => lines.sort_by_key(|(d, _)| (*d > start, Reverse(*d))),
+        }
+        Vaporization {
+            remaining: lines.iter().map(|(_, line)| line.len()).sum(),
+            lines,
+            rotation: 1,
+            index: 0,
+        }
     }
 
-    fn best_monitoring_station(&self) -> (usize, &Asteroid) {
-        let mut best = &Asteroid {
-            x: usize::MAX,
-            y: usize::MAX,
-        };
-        let mut best_score = 0;
-        for a in &self.asteroids {
-            debug!("{:?}", a);
-            let h = a.adjacent_asteroids(self.asteroids.clone());
-            if h.len() > best_score {
-                best_score = h.len();
-                best = a;
-            }
+    /// Number of asteroids seen from every asteroid, counted on `workers` threads
+    ///
+    /// Asteroids come in the order of the map. Every worker counts a slice of the map with a
+    /// single set of directions. With a single worker, they are counted on the calling thread.
+    fn visibility(&self, workers: usize) -> Vec<(&Asteroid, usize)> {
+        let asteroids = &self.asteroids;
+        if workers <= 1 || asteroids.len() < 2 {
+            return count_visible(asteroids, asteroids);
+        }
+        let size = asteroids.len().div_ceil(workers);
+        thread::scope(|s| {
+            let handles: Vec<_> = self
+                .asteroids
+                .chunks(size)
+                .map(|candidates| s.spawn(move || count_visible(candidates, asteroids)))
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().expect("Worker thread panicked"))
+                .collect()
+        })
+    }
+
+    /// The asteroid seeing the most asteroids, the first of the map among the ties
+    fn best_monitoring_station(&self, workers: usize) -> (usize, &Asteroid) {
+        self.visibility(workers)
+            .into_iter()
+            .fold(
+                None,
+                |best: Option<(usize, &Asteroid)>, (a, seen)| match best {
+                    Some((most, _)) if most >= seen => best,
+                    _ => Some((seen, a)),
+                },
+            )
+            .expect("No asteroid on the map")
+    }
+}
+
+fn gcd(a: i32, b: i32) -> i32 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+/// Direction from an asteroid to another, as the smallest integer vector pointing to it
+///
+/// Directions are ordered clockwise from up, the ordinates growing downwards.
+#[derive(Eq, PartialEq, Hash, Debug, Clone, Copy)]
+struct Direction {
+    dx: i32,
+    dy: i32,
+}
+
+impl Direction {
+    fn new(dx: i32, dy: i32) -> Self {
+        let g = gcd(dx.abs(), dy.abs());
+        assert!(g > 0, "No direction between an asteroid and itself");
+        Direction {
+            dx: dx / g,
+            dy: dy / g,
+        }
+    }
+
+    /// Returns true for the directions from up included to down excluded
+    fn is_right_half(&self) -> bool {
+        self.dx > 0 || (self.dx == 0 && self.dy < 0)
+    }
+
+    /// Positive when `other` comes after this direction in less than half a turn
+    fn cross(&self, other: &Direction) -> i64 {
+        i64::from(self.dx) * i64::from(other.dy) - i64::from(self.dy) * i64::from(other.dx)
+    }
+
+    /// Angle in degrees clockwise from up, for display only
+    fn bearing(&self) -> f64 {
+        let d = f64::from(self.dx).atan2(-f64::from(self.dy)).to_degrees();
+        if d < 0.0 { d + 360.0 } else { d }
+    }
+}
+
+impl FromStr for Direction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut coordinates = s.split(',').map(|c| c.trim().parse::<i32>());
+        match (coordinates.next(), coordinates.next(), coordinates.next()) {
+            (Some(Ok(dx)), Some(Ok(dy)), None) if dx != 0 || dy != 0 => Ok(Direction::new(dx, dy)),
+            _ => Err(format!("Invalid direction: {} (expected dx,dy)", s)),
+        }
+    }
+}
+
+impl Ord for Direction {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .is_right_half()
+            .cmp(&self.is_right_half())
+            .then_with(|| 0.cmp(&self.cross(other)))
+    }
+}
+
+impl PartialOrd for Direction {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Number of asteroids seen from every candidate
+fn count_visible<'a>(
+    candidates: &'a [Asteroid],
+    asteroids: &[Asteroid],
+) -> Vec<(&'a Asteroid, usize)> {
+    let mut seen = HashSet::new();
+    candidates
+        .iter()
+        .map(|a| (a, a.visible(asteroids, &mut seen)))
+        .collect()
+}
+
+/// Serializes the map back to `#` and `.`
+impl fmt::Display for AsteroidMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let asteroids: HashSet<_> = self.asteroids.iter().map(|a| (a.x, a.y)).collect();
+        for y in 0..self.height {
+            let line: String = (0..self.width)
+                .map(|x| {
+                    if asteroids.contains(&(x, y)) {
+                        '#'
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            writeln!(f, "{}", line)?;
         }
-        (best_score, best)
+        Ok(())
     }
 }
 
-#[derive(Default, Eq, Debug, Clone, Copy)]
+/// Size of a generated map
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Size {
+    width: usize,
+    height: usize,
+}
+
+impl FromStr for Size {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut sides = s.split('x').map(|n| n.trim().parse::<usize>());
+        match (sides.next(), sides.next(), sides.next()) {
+            (Some(Ok(width)), Some(Ok(height)), None) => Ok(Size { width, height }),
+            _ => Err(format!("Invalid size: {} (expected WIDTHxHEIGHT)", s)),
+        }
+    }
+}
+
+#[derive(Eq, Debug, Clone, Copy)]
 struct Asteroid {
     x: usize,
     y: usize,
@@ -87,35 +323,344 @@ impl Asteroid {
         ((self.x as i32 - o.x as i32).abs() + (self.y as i32 - o.y as i32).abs()) as usize
     }
 
-    fn angle(&self, o: &Asteroid) -> Decimal {
-        let x1 = (o.x as i32 - self.x as i32) as f64;
-        let y1 = (o.y as i32 - self.y as i32) as f64;
-        let mut d: f64 = y1.atan2(x1).to_degrees().into();
-        if d < 0.0 {
-            d = d + 360.0;
-        }
-        Decimal::from_f64(d).unwrap()
+    fn direction(&self, o: &Asteroid) -> Direction {
+        Direction::new(o.x as i32 - self.x as i32, o.y as i32 - self.y as i32)
     }
 
-    fn adjacent_asteroids(&self, asteroids: Vec<Asteroid>) -> HashMap<Decimal, Asteroid> {
-        let mut m: HashMap<Decimal, Asteroid> = HashMap::new();
+    /// Number of asteroids seen from this one, the closest asteroid of each direction hiding the
+    /// others, `seen` being reused between calls
+    fn visible(&self, asteroids: &[Asteroid], seen: &mut HashSet<Direction>) -> usize {
+        seen.clear();
+        seen.extend(
+            asteroids
+                .iter()
+                .filter(|a| *a != self)
+                .map(|a| self.direction(a)),
+        );
+        seen.len()
+    }
+
+    /// Other asteroids grouped by direction, the closest first
+    fn lines_of_sight(&self, asteroids: &[Asteroid]) -> HashMap<Direction, Vec<Asteroid>> {
+        let mut m: HashMap<Direction, Vec<Asteroid>> = HashMap::new();
         for a in asteroids {
-            if a == *self {
+            if a == self {
                 continue;
             }
-            let d: Decimal = self.angle(&a);
-            match m.get_mut(&d) {
-                Some(x) => {
-                    if self.distance(&a) < self.distance(&x) {
-                        *x = a;
+            m.entry(self.direction(a)).or_default().push(*a);
+        }
+        for line in m.values_mut() {
+            line.sort_by_key(|a| self.distance(a));
+        }
+        m
+    }
+}
+
+/// Sweep of the laser
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Rotation {
+    Clockwise,
+    CounterClockwise,
+}
+
+impl FromStr for Rotation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "clockwise" => Ok(Rotation::Clockwise),
+            "counter-clockwise" => Ok(Rotation::CounterClockwise),
+            _ => Err(format!(
+                "Invalid rotation: {} (expected clockwise or counter-clockwise)",
+                s
+            )),
+        }
+    }
+}
+
+/// An asteroid destroyed by the laser during the rotation `rotation`, starting at 1
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Vaporized {
+    asteroid: Asteroid,
+    rotation: usize,
+    direction: Direction,
+}
+
+/// Iterator over the asteroids destroyed by the laser
+///
+/// `lines` holds the asteroids left in every direction, in the order the laser points to
+/// them. Each rotation destroys the closest asteroid of every direction, and the directions
+/// left empty are dropped when a rotation ends.
+struct Vaporization {
+    lines: Vec<(Direction, VecDeque<Asteroid>)>,
+    rotation: usize,
+    index: usize,
+    remaining: usize,
+}
+
+impl Iterator for Vaporization {
+    type Item = Vaporized;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        if self.index == self.lines.len() {
+            self.lines.retain(|(_, line)| !line.is_empty());
+            self.rotation += 1;
+            self.index = 0;
+        }
+        let (direction, line) = &mut self.lines[self.index];
+        self.index += 1;
+        let asteroid = line.pop_front().expect("Empty line of sight");
+        self.remaining -= 1;
+        Some(Vaporized {
+            asteroid,
+            rotation: self.rotation,
+            direction: *direction,
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+/// Digits of 3 by 5 pixels, one row of 3 bits per line, for the numbers of the PPM heatmap
+const DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b001, 0b001, 0b001],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+type Colour = (u8, u8, u8);
+
+const SPACE: Colour = (0, 0, 0);
+const STATION: Colour = (255, 255, 255);
+
+/// Asteroid map shaded by the number of asteroids seen from every asteroid
+///
+/// Asteroids go from blue when they see the fewest asteroids to red when they see the most,
+/// the monitoring station being white. `order` numbers the asteroids in the order the laser
+/// vaporizes them when set.
+struct Heatmap {
+    width: usize,
+    height: usize,
+    seen: HashMap<(usize, usize), usize>,
+    range: (usize, usize),
+    station: Option<(usize, usize)>,
+    order: HashMap<(usize, usize), usize>,
+}
+
+impl Heatmap {
+    fn new(map: &AsteroidMap, workers: usize) -> Self {
+        let seen: HashMap<_, _> = map
+            .visibility(workers)
+            .into_iter()
+            .map(|(a, seen)| ((a.x, a.y), seen))
+            .collect();
+        let range = (
+            seen.values().min().cloned().unwrap_or(0),
+            seen.values().max().cloned().unwrap_or(0),
+        );
+        // the first of the map among the ties, like `best_monitoring_station`
+        let station = map
+            .asteroids
+            .iter()
+            .rev()
+            .max_by_key(|a| seen[&(a.x, a.y)])
+            .map(|a| (a.x, a.y));
+        Heatmap {
+            width: map.width,
+            height: map.height,
+            seen,
+            range,
+            station,
+            order: HashMap::new(),
+        }
+    }
+
+    /// Numbers the asteroids in the order they are vaporized
+    fn numbered<I: Iterator<Item = Vaporized>>(mut self, vaporization: I) -> Self {
+        self.order = vaporization
+            .enumerate()
+            .map(|(n, v)| ((v.asteroid.x, v.asteroid.y), n + 1))
+            .collect();
+        self
+    }
+
+    /// Characters needed by the widest number
+    fn digits(&self) -> usize {
+        self.order.len().to_string().len()
+    }
+
+    fn colour(&self, cell: (usize, usize)) -> Colour {
+        if Some(cell) == self.station {
+            return STATION;
+        }
+        match self.seen.get(&cell) {
+            None => SPACE,
+            Some(seen) => {
+                let (low, high) = self.range;
+                let t = if high > low {
+                    (seen - low) as f64 / (high - low) as f64
+                } else {
+                    1.0
+                };
+                ((255.0 * t) as u8, 48, (255.0 * (1.0 - t)) as u8)
+            },
+        }
+    }
+
+    /// Colour of the text written over `background`
+    fn ink(background: Colour) -> Colour {
+        let (r, g, b) = background;
+        if 0.299 * f64::from(r) + 0.587 * f64::from(g) + 0.114 * f64::from(b) > 128.0 {
+            SPACE
+        } else {
+            STATION
+        }
+    }
+
+    /// Returns the map with ANSI colours, the station being `@` and the other asteroids `#` or
+    /// their number
+    fn terminal(&self) -> String {
+        let width = if self.order.is_empty() {
+            1
+        } else {
+            self.digits()
+        };
+        let mut out = String::new();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let cell = (x, y);
+                if !self.seen.contains_key(&cell) {
+                    out += &format!("{:>1$}", ".", width);
+                    continue;
+                }
+                let text = match self.order.get(&cell) {
+                    _ if Some(cell) == self.station => "@".to_string(),
+                    Some(n) => n.to_string(),
+                    None => "#".to_string(),
+                };
+                let (r, g, b) = self.colour(cell);
+                let (fr, fg, fb) = Heatmap::ink((r, g, b));
+                out += &format!(
+                    "\x1b[48;2;{};{};{}m\x1b[38;2;{};{};{}m{:>7$}\x1b[0m",
+                    r, g, b, fr, fg, fb, text, width
+                );
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Returns the map as a binary PPM image with `scale` pixels per cell, widened to fit the
+    /// numbers
+    fn ppm(&self, scale: usize) -> Vec<u8> {
+        let size = if self.order.is_empty() {
+            scale.max(1)
+        } else {
+            scale.max(4 * self.digits() + 1).max(7)
+        };
+        let (width, height) = (self.width * size, self.height * size);
+        let mut pixels = vec![SPACE; width * height];
+        for (&(x, y), _) in self.seen.iter() {
+            let colour = self.colour((x, y));
+            for py in y * size..(y + 1) * size {
+                for px in x * size..(x + 1) * size {
+                    pixels[py * width + px] = colour;
+                }
+            }
+            if let Some(n) = self.order.get(&(x, y)) {
+                let text = n.to_string();
+                let ink = Heatmap::ink(colour);
+                let left = x * size + (size - (4 * text.len() - 1)) / 2;
+                let top = y * size + (size - 5) / 2;
+                for (i, digit) in text.bytes().enumerate() {
+                    let glyph = DIGITS[usize::from(digit - b'0')];
+                    for (row, bits) in glyph.iter().enumerate() {
+                        for column in 0..3 {
+                            if bits & (0b100 >> column) != 0 {
+                                pixels[(top + row) * width + left + 4 * i + column] = ink;
+                            }
+                        }
                     }
-                },
-                None => {
-                    m.insert(d, a);
-                },
+                }
             }
         }
-        m
+        let mut out = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+        for (r, g, b) in pixels {
+            out.extend_from_slice(&[r, g, b]);
+        }
+        out
+    }
+}
+
+fn main() {
+    advent::init_logging();
+    let opt = Opt::from_args();
+    let m = match (opt.generate, opt.input) {
+        (Some(size), _) => AsteroidMap::generate(size, opt.density, opt.seed),
+        (None, Some(input)) => {
+            AsteroidMap::new(fs::read_to_string(input).expect("Unable to read input file"))
+        },
+        (None, None) => unreachable!("The input file is required without --generate"),
+    };
+    if opt.print {
+        print!("{}", m);
+    }
+    info!("Asteroid count: {:?}", m.count());
+    let workers = if opt.sequential {
+        1
+    } else {
+        opt.workers.unwrap_or_else(default_workers)
+    };
+    if opt.visibility {
+        for (a, seen) in m.visibility(workers) {
+            println!("{},{}: {}", a.x, a.y, seen);
+        }
+    }
+    let (best_score, best) = m.best_monitoring_station(workers);
+    info!("Asteroid best: {:?} with {:?}", best, best_score);
+    if opt.colour || opt.heatmap.is_some() {
+        let mut heatmap = Heatmap::new(&m, workers);
+        if opt.numbers {
+            heatmap = heatmap.numbered(m.vaporization(best, opt.rotation, opt.start));
+        }
+        if opt.colour {
+            print!("{}", heatmap.terminal());
+        }
+        if let Some(path) = opt.heatmap {
+            fs::write(path, heatmap.ppm(opt.scale)).expect("Unable to write heatmap");
+        }
+    }
+    if opt.order {
+        for (n, v) in m.vaporization(best, opt.rotation, opt.start).enumerate() {
+            println!(
+                "{:>4}: {},{} rotation {} at {:.2}°",
+                n + 1,
+                v.asteroid.x,
+                v.asteroid.y,
+                v.rotation,
+                v.direction.bearing()
+            );
+        }
+    }
+    match m.vaporization(best, opt.rotation, opt.start).nth(199) {
+        None => info!("fewer than 200 asteroids to vaporize"),
+        Some(v) => {
+            let a = v.asteroid;
+            info!("200th asteroid vaporized: {:?}", a);
+            info!("Answer part 2: {:?}", a.x * 100 + a.y)
+        },
     }
 }
 
@@ -136,22 +681,53 @@ mod tests {
         assert_eq!(b_map.asteroids[2].y, 1);
     }
     #[test]
-    fn test_asteroid_angle() {
+    fn test_asteroid_direction() {
         assert_eq!(
-            Asteroid { x: 0, y: 0 }.angle(&Asteroid { x: 0, y: 1 }),
-            Decimal::from_str("90.0").unwrap()
+            Asteroid { x: 0, y: 0 }.direction(&Asteroid { x: 0, y: 1 }),
+            Direction { dx: 0, dy: 1 }
         );
         assert_eq!(
-            Asteroid { x: 0, y: 0 }.angle(&Asteroid { x: 1, y: 1 }),
-            Decimal::from_str("45.0").unwrap()
+            Asteroid { x: 0, y: 0 }.direction(&Asteroid { x: 3, y: 3 }),
+            Direction { dx: 1, dy: 1 }
         );
         assert_eq!(
-            Asteroid { x: 1, y: 1 }.angle(&Asteroid { x: 0, y: 0 }),
-            Decimal::from_str("225.0").unwrap()
+            Asteroid { x: 6, y: 4 }.direction(&Asteroid { x: 0, y: 0 }),
+            Direction { dx: -3, dy: -2 }
         );
         assert_eq!(
-            Asteroid { x: 1, y: 1 }.angle(&Asteroid { x: 0, y: 1 }),
-            Decimal::from_str("180.0").unwrap()
+            Asteroid { x: 1, y: 1 }
+                .direction(&Asteroid { x: 0, y: 1 })
+                .bearing(),
+            270.0
+        );
+        assert_eq!(Direction::new(1, 1).bearing(), 135.0);
+    }
+
+    #[test]
+    fn test_direction_order() {
+        // clockwise from up, the ordinates growing downwards
+        let clockwise = vec![
+            Direction::new(0, -1),
+            Direction::new(1, -1000),
+            Direction::new(1, -1),
+            Direction::new(1, 0),
+            Direction::new(1, 1),
+            Direction::new(0, 1),
+            Direction::new(-1, 1),
+            Direction::new(-1, 0),
+            Direction::new(-1000, -999),
+            Direction::new(-999, -1000),
+        ];
+        let mut sorted = clockwise.clone();
+        sorted.reverse();
+        sorted.sort();
+        assert_eq!(sorted, clockwise);
+        // nearly aligned directions stay apart, multiples merge
+        assert_ne!(Direction::new(9999, 10000), Direction::new(10000, 10001));
+        assert_eq!(Direction::new(-4000, 6000), Direction::new(-2, 3));
+        assert_eq!(
+            Direction::new(9999, 10000).cmp(&Direction::new(10000, 10001)),
+            Ordering::Greater
         );
     }
 
@@ -168,15 +744,373 @@ mod tests {
     }
 
     #[test]
-    fn test_adjacent_asteroids() {
-        let m = Asteroid { x: 0, y: 0 }.adjacent_asteroids(vec![
-            Asteroid { x: 1, y: 0 },
+    fn test_visibility() {
+        let a_map = AsteroidMap::new(".#..#\n.....\n#####\n....#\n...##".to_string());
+        let expected = vec![7, 7, 6, 7, 7, 7, 5, 7, 8, 7];
+        for workers in &[1, 2, 3, 16] {
+            let visibility = a_map.visibility(*workers);
+            assert_eq!(
+                visibility.iter().map(|(_, seen)| *seen).collect::<Vec<_>>(),
+                expected
+            );
+            assert_eq!(visibility[8].0, &Asteroid { x: 3, y: 4 });
+            assert_eq!(
+                a_map.best_monitoring_station(*workers),
+                (8, &Asteroid { x: 3, y: 4 })
+            );
+        }
+        assert_eq!(AsteroidMap::new("#".to_string()).visibility(4)[0].1, 0);
+    }
+
+    #[test]
+    fn test_heatmap() {
+        let a_map = AsteroidMap::new(".#..#\n.....\n#####\n....#\n...##".to_string());
+        let heatmap = Heatmap::new(&a_map, 1);
+        assert_eq!(heatmap.station, Some((3, 4)));
+        assert_eq!(heatmap.colour((4, 2)), (0, 48, 255));
+        assert_eq!(heatmap.colour((3, 4)), STATION);
+        assert_eq!(heatmap.colour((0, 0)), SPACE);
+        let terminal = heatmap.terminal();
+        assert_eq!(terminal.lines().count(), 5);
+        assert!(terminal.starts_with(
+            ".\x1b[48;2;170;48;85m\x1b[38;2;255;255;255m#\x1b[0m..\x1b[48;2;170;48;85m"
+        ));
+        assert!(terminal.contains("\x1b[38;2;0;0;0m@\x1b[0m"));
+
+        let ppm = heatmap.ppm(2);
+        let header = b"P6\n10 10\n255\n";
+        assert_eq!(&ppm[..header.len()], header);
+        assert_eq!(ppm.len(), header.len() + 10 * 10 * 3);
+        let pixel = |image: &[u8], width: usize, x: usize, y: usize| {
+            let i = image.len() - width * width * 3 + (y * width + x) * 3;
+            (image[i], image[i + 1], image[i + 2])
+        };
+        assert_eq!(pixel(&ppm, 10, 7, 9), STATION);
+        assert_eq!(pixel(&ppm, 10, 1, 1), SPACE);
+
+        // (3, 2) is the first asteroid vaporized, its 1 drawn in the middle of a 7 pixels cell
+        let station = Asteroid { x: 3, y: 4 };
+        let numbered = Heatmap::new(&a_map, 1).numbered(a_map.vaporization(
+            &station,
+            Rotation::Clockwise,
+            Direction::new(0, -1),
+        ));
+        assert_eq!(numbered.order[&(3, 2)], 1);
+        assert!(numbered.terminal().contains("m1\x1b[0m"));
+        let ppm = numbered.ppm(2);
+        assert_eq!(pixel(&ppm, 35, 24, 15), STATION);
+        assert_eq!(pixel(&ppm, 35, 23, 15), numbered.colour((3, 2)));
+    }
+
+    /// Maps of various sizes and densities, the same for every run
+    fn generated_maps() -> Vec<AsteroidMap> {
+        (0..40)
+            .map(|seed| {
+                let size = Size {
+                    width: 1 + (seed as usize * 7) % 23,
+                    height: 1 + (seed as usize * 5) % 17,
+                };
+                AsteroidMap::generate(size, 0.1 + (seed % 8) as f64 * 0.1, seed)
+            })
+            .collect()
+    }
+
+    /// Angle of `to` seen from `from` in degrees clockwise from up, the floating-point way
+    fn bearing(from: &Asteroid, to: &Asteroid) -> f64 {
+        let dx = to.x as f64 - from.x as f64;
+        let dy = to.y as f64 - from.y as f64;
+        let d = dx.atan2(-dy).to_degrees();
+        if d < 0.0 { d + 360.0 } else { d }
+    }
+
+    #[test]
+    fn test_generate() {
+        let size = Size {
+            width: 30,
+            height: 20,
+        };
+        let a_map = AsteroidMap::generate(size, 0.25, 42);
+        assert_eq!(
+            a_map.to_string(),
+            AsteroidMap::generate(size, 0.25, 42).to_string()
+        );
+        assert_ne!(
+            a_map.to_string(),
+            AsteroidMap::generate(size, 0.25, 43).to_string()
+        );
+        assert!(a_map.count() > 100 && a_map.count() < 200);
+        assert_eq!(AsteroidMap::generate(size, 0.0, 1).count(), 0);
+        assert_eq!(AsteroidMap::generate(size, 1.0, 1).count(), 600);
+        assert_eq!("30x20".parse(), Ok(size));
+        assert!("30".parse::<Size>().is_err());
+
+        for a_map in generated_maps() {
+            let text = a_map.to_string();
+            let parsed = AsteroidMap::new(text.clone());
+            assert_eq!(parsed.asteroids, a_map.asteroids);
+            assert_eq!((parsed.width, parsed.height), (a_map.width, a_map.height));
+            assert_eq!(parsed.to_string(), text);
+        }
+    }
+
+    #[test]
+    fn test_visibility_is_symmetric() {
+        for a_map in generated_maps() {
+            let visible: HashSet<_> = a_map
+                .asteroids
+                .iter()
+                .flat_map(|a| {
+                    a.lines_of_sight(&a_map.asteroids)
+                        .into_values()
+                        .map(move |line| ((a.x, a.y), (line[0].x, line[0].y)))
+                })
+                .collect();
+            for (a, b) in &visible {
+                assert!(
+                    visible.contains(&(*b, *a)),
+                    "{:?} sees {:?}\n{}",
+                    a,
+                    b,
+                    a_map
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_vaporization_is_a_permutation() {
+        let mut random = Random::new(7);
+        for a_map in generated_maps().into_iter().filter(|m| m.count() > 0) {
+            let station = a_map.asteroids[random.next_u64() as usize % a_map.count()];
+            let start = Direction::new(
+                (random.next_u64() % 21) as i32 - 10,
+                (random.next_u64() % 20) as i32 + 1,
+            );
+            let mut others: Vec<_> = a_map
+                .asteroids
+                .iter()
+                .filter(|a| **a != station)
+                .map(|a| (a.x, a.y))
+                .collect();
+            others.sort();
+            for rotation in &[Rotation::Clockwise, Rotation::CounterClockwise] {
+                let mut destroyed: Vec<_> = a_map
+                    .vaporization(&station, *rotation, start)
+                    .map(|v| (v.asteroid.x, v.asteroid.y))
+                    .collect();
+                destroyed.sort();
+                assert_eq!(destroyed, others, "from {:?}\n{}", station, a_map);
+            }
+        }
+    }
+
+    /// Checks the exact directions against a reference model written for this test, which
+    /// groups the asteroids by floating-point bearing; it is not the angle implementation the
+    /// solver started from
+    #[test]
+    fn test_exact_and_angles_agree() {
+        let large = AsteroidMap::generate(
+            Size {
+                width: 60,
+                height: 60,
+            },
+            0.2,
+            2019,
+        );
+        for a_map in generated_maps().into_iter().chain(std::iter::once(large)) {
+            for (a, seen) in a_map.visibility(2) {
+                let mut angles: Vec<f64> = a_map
+                    .asteroids
+                    .iter()
+                    .filter(|b| *b != a)
+                    .map(|b| bearing(a, b))
+                    .collect();
+                angles.sort_by(|x, y| x.partial_cmp(y).unwrap());
+                angles.dedup_by(|x, y| (*x - *y).abs() < 1e-9);
+                assert_eq!(seen, angles.len(), "from {:?}\n{}", a, a_map);
+            }
+            let station = match a_map.asteroids.first() {
+                Some(a) => *a,
+                None => continue,
+            };
+            // grouped by rounded bearing, vaporized by rotation, then clockwise from up
+            let mut groups: HashMap<i64, Vec<Asteroid>> = HashMap::new();
+            for b in a_map.asteroids.iter().filter(|b| **b != station) {
+                let key = (bearing(&station, b) * 1e6).round() as i64;
+                groups.entry(key).or_default().push(*b);
+            }
+            let distance = |b: &Asteroid| {
+                let (dx, dy) = (b.x as f64 - station.x as f64, b.y as f64 - station.y as f64);
+                dx * dx + dy * dy
+            };
+            let mut expected: Vec<_> = groups
+                .into_values()
+                .flat_map(|mut group| {
+                    group.sort_by(|x, y| distance(x).partial_cmp(&distance(y)).unwrap());
+                    group
+                        .into_iter()
+                        .enumerate()
+                        .map(|(rotation, b)| (rotation, bearing(&station, &b), b))
+                        .collect::<Vec<_>>()
+                })
+                .collect();
+            expected.sort_by(|x, y| (x.0, x.1).partial_cmp(&(y.0, y.1)).unwrap());
+            let order: Vec<_> = a_map
+                .vaporization(&station, Rotation::Clockwise, Direction::new(0, -1))
+                .collect();
+            assert_eq!(
+                order.iter().map(|v| v.asteroid).collect::<Vec<_>>(),
+                expected.iter().map(|e| e.2).collect::<Vec<_>>()
+            );
+            for (v, e) in order.iter().zip(&expected) {
+                assert_eq!(v.rotation, e.0 + 1);
+                assert!((v.direction.bearing() - e.1).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn test_lines_of_sight() {
+        let m = Asteroid { x: 0, y: 0 }.lines_of_sight(&[
             Asteroid { x: 2, y: 0 },
+            Asteroid { x: 1, y: 0 },
             Asteroid { x: 0, y: 0 },
         ]);
         assert_eq!(m.len(), 1);
-        // assert_eq!(*m.keys().next().unwrap(), Decimal::from_str("0.0").unwrap());
-        assert_eq!(*m.values().next().unwrap(), Asteroid { x: 1, y: 0 });
+        assert_eq!(*m.keys().next().unwrap(), Direction { dx: 1, dy: 0 });
+        assert_eq!(
+            *m.values().next().unwrap(),
+            vec![Asteroid { x: 1, y: 0 }, Asteroid { x: 2, y: 0 }]
+        );
+    }
+
+    #[test]
+    fn test_vaporization() {
+        let a_map = AsteroidMap::new(
+            ".#..##.###...#######
+##.############..##.
+.#.######.########.#
+.###.#######.####.#.
+#####.##.#.##.###.##
+..#####..#.#########
+####################
+#.####....###.#.#.##
+##.#################
+#####.##.###..####..
+..######..##.#######
+####.##.####...##..#
+.#####..#.######.###
+##...#.##########...
+#.##########.#######
+.####.#.###.###.#.##
+....##.##.###..#####
+.#.#.###########.###
+#.#.#.#####.####.###
+###.##.####.##.#..##"
+                .to_string(),
+        );
+        let (seen, station) = a_map.best_monitoring_station(1);
+        assert_eq!((seen, *station), (210, Asteroid { x: 11, y: 13 }));
+        let order: Vec<_> = a_map
+            .vaporization(station, Rotation::Clockwise, Direction::new(0, -1))
+            .collect();
+        assert_eq!(order.len(), a_map.count() - 1);
+        let expected = vec![
+            (1, 11, 12),
+            (2, 12, 1),
+            (3, 12, 2),
+            (10, 12, 8),
+            (20, 16, 0),
+            (50, 16, 9),
+            (100, 10, 16),
+            (199, 9, 6),
+            (200, 8, 2),
+            (201, 10, 9),
+            (299, 11, 1),
+        ];
+        for (n, x, y) in expected {
+            assert_eq!(order[n - 1].asteroid, Asteroid { x, y }, "{}th", n);
+        }
+        assert_eq!(order[0].rotation, 1);
+        assert_eq!(order[0].direction, Direction::new(0, -1));
+        assert_eq!(order[298].rotation, order.last().unwrap().rotation);
+        assert!(order.windows(2).all(|w| w[0].rotation <= w[1].rotation));
+    }
+
+    #[test]
+    fn test_vaporization_sweeps() {
+        let a_map = AsteroidMap::new("###\n###\n###".to_string());
+        let station = Asteroid { x: 1, y: 1 };
+        let order = |rotation, start| {
+            a_map
+                .vaporization(&station, rotation, start)
+                .map(|v| (v.asteroid.x, v.asteroid.y))
+                .collect::<Vec<_>>()
+        };
+        let up = Direction::new(0, -1);
+        let right = Direction::new(1, 0);
+        assert_eq!(
+            order(Rotation::Clockwise, up),
+            vec![
+                (1, 0),
+                (2, 0),
+                (2, 1),
+                (2, 2),
+                (1, 2),
+                (0, 2),
+                (0, 1),
+                (0, 0)
+            ]
+        );
+        assert_eq!(
+            order(Rotation::CounterClockwise, up),
+            vec![
+                (1, 0),
+                (0, 0),
+                (0, 1),
+                (0, 2),
+                (1, 2),
+                (2, 2),
+                (2, 1),
+                (2, 0)
+            ]
+        );
+        assert_eq!(
+            order(Rotation::Clockwise, right),
+            vec![
+                (2, 1),
+                (2, 2),
+                (1, 2),
+                (0, 2),
+                (0, 1),
+                (0, 0),
+                (1, 0),
+                (2, 0)
+            ]
+        );
+        // a laser starting between two asteroids hits the next one of the sweep first
+        let between = Direction::new(1, 2);
+        assert_eq!(order(Rotation::Clockwise, between)[0], (1, 2));
+        assert_eq!(order(Rotation::CounterClockwise, between)[0], (2, 2));
+        assert_eq!("-1,0".parse(), Ok(Direction::new(-1, 0)));
+        assert!("0,0".parse::<Direction>().is_err());
+        assert_eq!("counter-clockwise".parse(), Ok(Rotation::CounterClockwise));
+    }
+
+    #[test]
+    fn test_vaporization_large_map() {
+        let row = "#".repeat(100);
+        let a_map = AsteroidMap::new(vec![row; 100].join("\n"));
+        let station = Asteroid { x: 50, y: 50 };
+        let order: Vec<_> = a_map
+            .vaporization(&station, Rotation::Clockwise, Direction::new(0, -1))
+            .collect();
+        assert_eq!(order.len(), 9999);
+        let destroyed: HashSet<_> = order.iter().map(|v| (v.asteroid.x, v.asteroid.y)).collect();
+        assert_eq!(destroyed.len(), 9999);
+        // the last one is the farthest of the most crowded line, the diagonal to the top left
+        assert_eq!(order.last().unwrap().asteroid, Asteroid { x: 0, y: 0 });
+        assert_eq!(order.last().unwrap().rotation, 50);
     }
 
     #[test]
@@ -190,50 +1124,13 @@ mod tests {
 ..#.#.....#....##"
                 .to_string(),
         );
-        let (_, best) = a_map.best_monitoring_station();
-        let nineth = vaporize(a_map.clone(), *best, 9);
-        assert_eq!(nineth, Asteroid { x: 15, y: 1 });
-        let n2 = vaporize(a_map.clone(), *best, 18);
-        assert_eq!(n2, Asteroid { x: 4, y: 4 });
-        let last_vaporized = vaporize(a_map.clone(), *best, 200);
-        assert_eq!(last_vaporized, Asteroid { x: 14, y: 3 })
-    }
-}
-
-fn vaporize(mut m: AsteroidMap, a: Asteroid, mut count: usize) -> Asteroid {
-    debug!("Vaporize from {:?}", a);
-    let h = a.adjacent_asteroids(m.asteroids.clone());
-    let mut v: Vec<Decimal> = h.keys().cloned().collect::<Vec<Decimal>>();
-    v.sort();
-    debug!("angles: {:?}", v);
-    let p = v
-        .iter()
-        .position(|&x| x == Decimal::from_str("270.0").unwrap());
-    match p {
-        Some(x) => v.rotate_left(x),
-        None => (),
-    };
-    for angle in v {
-        debug!("{:?} at {:?} (size: {:?})", count, angle, m.count());
-        let a = h[&angle];
-        m.vaporize(a);
-        count = count - 1;
-        if count == 0 || m.count() == 1 {
-            return a;
-        }
+        let (_, best) = a_map.best_monitoring_station(1);
+        let order: Vec<Asteroid> = a_map
+            .vaporization(best, Rotation::Clockwise, Direction::new(0, -1))
+            .map(|v| v.asteroid)
+            .collect();
+        assert_eq!(order[8], Asteroid { x: 15, y: 1 });
+        assert_eq!(order[17], Asteroid { x: 4, y: 4 });
+        assert_eq!(*order.last().unwrap(), Asteroid { x: 14, y: 3 })
     }
-    return vaporize(m, a, count);
-}
-
-fn main() {
-    advent::init_logging();
-    let opt = Opt::from_args();
-    let buffer: String = fs::read_to_string(opt.input).expect("Unable to read input file");
-    let m = AsteroidMap::new(buffer);
-    info!("Asteroid count: {:?}", m.count());
-    let (best_score, best) = m.best_monitoring_station();
-    info!("Asteroid best: {:?} with {:?}", best, best_score);
-    let a = vaporize(m.clone(), *best, 200);
-    info!("200th asteroid vaporized: {:?}", a);
-    info!("Answer part 2: {:?}", a.x * 100 + a.y)
 }
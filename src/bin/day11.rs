@@ -0,0 +1,28 @@
+use advent::hull::{Colour, Robot};
+use advent::intcode::Intcode;
+use log::*;
+use std::fs;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "day11", about = "Advent of Code - Day 11")]
+struct Opt {
+    /// Colour of the starting panel: black or white
+    #[structopt(short, long, default_value = "black")]
+    start: Colour,
+
+    /// Input file
+    #[structopt(parse(from_os_str))]
+    input: PathBuf,
+}
+
+fn main() {
+    let opt = Opt::from_args();
+    advent::init_logging();
+    let buffer = fs::read_to_string(opt.input).expect("Unable to read input file");
+    let mut robot = Robot::new(opt.start);
+    robot.run(&mut Intcode::new(buffer));
+    info!("painted panels: {}", robot.painted());
+    print!("{}", robot.render());
+}
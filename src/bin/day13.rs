@@ -0,0 +1,66 @@
+use advent::arcade::{self, Autopilot, Cabinet, Keyboard, Screen};
+use advent::intcode::{Intcode, State};
+use log::*;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "day13", about = "Advent of Code - Day 13")]
+struct Opt {
+    /// Play the game for free instead of only drawing the first screen
+    #[structopt(short, long)]
+    play: bool,
+
+    /// Move the joystick from the keyboard (a/h: left, d/l: right, enter: neutral)
+    #[structopt(short, long)]
+    interactive: bool,
+
+    /// Play on autopilot without rendering any frame
+    #[structopt(long)]
+    headless: bool,
+
+    /// Delay between two frames, in milliseconds
+    #[structopt(long, default_value = "0")]
+    delay: u64,
+
+    /// Input file
+    #[structopt(parse(from_os_str))]
+    input: PathBuf,
+}
+
+fn show(screen: &Screen, delay: u64) {
+    print!("\x1b[2J\x1b[H{}", screen.render());
+    thread::sleep(Duration::from_millis(delay));
+}
+
+fn main() {
+    let opt = Opt::from_args();
+    advent::init_logging();
+    let buffer = fs::read_to_string(opt.input).expect("Unable to read input file");
+    let program = Intcode::new(buffer);
+    if opt.headless {
+        let outcome = arcade::headless(program);
+        info!("score: {} blocks: {}", outcome.score, outcome.blocks);
+        return;
+    }
+    let mut cabinet = Cabinet::new(program);
+    if opt.play {
+        cabinet.insert_quarters();
+    }
+    let delay = opt.delay;
+    let state = if opt.interactive {
+        let stdin = io::stdin();
+        cabinet.play(&mut Keyboard(stdin.lock()), |s| show(s, delay))
+    } else {
+        cabinet.play(&mut Autopilot, |s| show(s, delay))
+    };
+    if state == State::WaitingForInput {
+        info!("game left before the end");
+    }
+    info!("score: {}", cabinet.screen().score());
+    info!("blocks: {}", cabinet.screen().blocks());
+}
@@ -0,0 +1,38 @@
+use advent::droid::Map;
+use advent::grid::Point;
+use advent::intcode::Intcode;
+use log::*;
+use std::fs;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "day15", about = "Advent of Code - Day 15")]
+struct Opt {
+    /// Print the map discovered by the droid
+    #[structopt(short, long)]
+    render: bool,
+
+    /// Input file
+    #[structopt(parse(from_os_str))]
+    input: PathBuf,
+}
+
+fn main() {
+    let opt = Opt::from_args();
+    advent::init_logging();
+    let buffer = fs::read_to_string(opt.input).expect("Unable to read input file");
+    let map = Map::explore(&mut Intcode::new(buffer));
+    let oxygen = map
+        .oxygen()
+        .expect("The droid did not find the oxygen system");
+    info!("oxygen system at {}", oxygen);
+    info!(
+        "moves to the oxygen system: {}",
+        map.shortest_path(Point::default(), oxygen).unwrap()
+    );
+    info!("minutes to fill with oxygen: {}", map.fill_time().unwrap());
+    if opt.render {
+        print!("{}", map.render());
+    }
+}
@@ -0,0 +1,43 @@
+use advent::intcode::Intcode;
+use advent::scaffold::{self, Camera};
+use log::*;
+use std::fs;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "day17", about = "Advent of Code - Day 17")]
+struct Opt {
+    /// Ask the robot for a continuous video feed while it moves
+    #[structopt(short, long)]
+    video: bool,
+
+    /// Input file
+    #[structopt(parse(from_os_str))]
+    input: PathBuf,
+}
+
+fn main() {
+    let opt = Opt::from_args();
+    advent::init_logging();
+    let buffer = fs::read_to_string(opt.input).expect("Unable to read input file");
+    let program = Intcode::new(buffer);
+    let camera = Camera::capture(&mut program.clone());
+    info!("alignment parameters: {}", camera.alignment());
+    let path = camera.path();
+    let routines = scaffold::compress(&path).expect("Unable to compress the path");
+    info!("main routine: {}", routines.main);
+    for (name, function) in ["A", "B", "C"].iter().zip(&routines.functions) {
+        info!("function {}: {}", name, function);
+    }
+    let reply = scaffold::deliver(program, &routines, opt.video);
+    if opt.video {
+        print!("{}", reply.text);
+    } else {
+        debug!("{}", reply.text);
+    }
+    match reply.value {
+        Some(dust) => info!("collected dust: {}", dust),
+        None => error!("the robot did not report any dust"),
+    }
+}
@@ -0,0 +1,58 @@
+use advent::beam::{self, Scanner};
+use advent::intcode::Intcode;
+use log::*;
+use std::fs;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "day19", about = "Advent of Code - Day 19")]
+struct Opt {
+    /// Size of the area close to the emitter to look at
+    #[structopt(short, long, default_value = "50")]
+    area: i64,
+
+    /// Size of the square ship to fit in the beam
+    #[structopt(short, long, default_value = "100")]
+    ship: i64,
+
+    /// Print the area close to the emitter, probing every position of it
+    #[structopt(short, long)]
+    render: bool,
+
+    /// Input file
+    #[structopt(parse(from_os_str))]
+    input: PathBuf,
+}
+
+fn main() {
+    let opt = Opt::from_args();
+    advent::init_logging();
+    let buffer = fs::read_to_string(opt.input).expect("Unable to read input file");
+    let program = Intcode::new(buffer);
+    let mut scanner = Scanner::new(program.clone());
+    info!(
+        "points affected by the beam: {} ({} probes)",
+        scanner.affected(opt.area),
+        scanner.probes()
+    );
+    let mut scanner = Scanner::new(program.clone());
+    match scanner.closest_square(opt.ship) {
+        Some(square) => info!(
+            "closest square at {}: {} ({} probes)",
+            square,
+            square.x * 10000 + square.y,
+            scanner.probes()
+        ),
+        None => error!(
+            "no {} by {} square fits in the beam ({} probes)",
+            opt.ship,
+            opt.ship,
+            scanner.probes()
+        ),
+    }
+    if opt.render {
+        let mut scanner = Scanner::new(program);
+        print!("{}", beam::render(&scanner.scan(opt.area, opt.area)));
+    }
+}
@@ -1,5 +1,7 @@
+use advent::intcode::{Intcode as SharedIntcode, State};
 use log::*;
 use std::io;
+use structopt::StructOpt;
 
 #[derive(Debug, Clone)]
 struct Intcode {
@@ -96,12 +98,37 @@ fn run(code: String, noun: i32, verb: i32) -> i32 {
     program.run()
 }
 
+/// Runs the program on the shared computer, then prints the writes to its own instructions
+fn analyze(code: String, noun: i64, verb: i64) {
+    let mut program = SharedIntcode::new(code);
+    program.write(1, noun);
+    program.write(2, verb);
+    program.enable_patch_detection();
+    if program.run_with(|| None, |_| ()) == State::WaitingForInput {
+        println!("program stopped waiting for an input");
+    }
+    let patches = program.patches().expect("Patch detection not enabled");
+    print!("{}", patches.summary());
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "day2", about = "Advent of Code - Day 2")]
+struct Opt {
+    /// Report the writes of the program to its own instructions
+    #[structopt(short, long)]
+    analyze: bool,
+}
+
 fn main() {
+    let opt = Opt::from_args();
     advent::init_logging();
     let mut buffer = String::new();
     io::stdin()
         .read_line(&mut buffer)
         .expect("Failed to read input !");
+    if opt.analyze {
+        analyze(buffer.clone(), 12, 2);
+    }
     let mut result = run(buffer.clone(), 12, 2);
     info!("Step 1 first item: {}", result);
     for noun in 0..99 {
@@ -0,0 +1,49 @@
+use advent::intcode::Intcode;
+use advent::springscript::{self, Mode, Outcome, Script};
+use log::*;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "day21", about = "Advent of Code - Day 21")]
+struct Opt {
+    /// Springdroid mode: walk or run
+    #[structopt(short, long, default_value = "walk")]
+    mode: Mode,
+
+    /// Springscript to run, synthesized from the falls of the droid when missing
+    #[structopt(short, long, parse(from_os_str))]
+    script: Option<PathBuf>,
+
+    /// Input file
+    #[structopt(parse(from_os_str))]
+    input: PathBuf,
+}
+
+fn main() {
+    let opt = Opt::from_args();
+    advent::init_logging();
+    let buffer = fs::read_to_string(opt.input).expect("Unable to read input file");
+    let program = Intcode::new(buffer);
+    let mode = opt.mode;
+    let result = match opt.script {
+        Some(path) => fs::read_to_string(path)
+            .expect("Unable to read script file")
+            .parse::<Script>()
+            .and_then(|script| springscript::run(program, &script, mode)),
+        None => springscript::solve(&program, mode).map(|(script, damage)| {
+            info!("synthesized script:\n{}", script);
+            Outcome::Damage(damage)
+        }),
+    };
+    match result {
+        Ok(Outcome::Damage(damage)) => info!("hull damage: {}", damage),
+        Ok(Outcome::Fall(fall)) => print!("{}", fall),
+        Err(e) => {
+            error!("{}", e);
+            process::exit(1);
+        },
+    }
+}
@@ -0,0 +1,93 @@
+use advent::adventure::{self, Game};
+use advent::intcode::Intcode;
+use log::*;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::process;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "day25", about = "Advent of Code - Day 25")]
+struct Opt {
+    /// Explore the ship and pass the checkpoint without asking for commands
+    #[structopt(short, long)]
+    auto: bool,
+
+    /// Input file
+    #[structopt(parse(from_os_str))]
+    input: PathBuf,
+}
+
+const HELP: &str = "Shell commands:
+  !save <name>  save the game
+  !load <name>  restore a saved game
+  !history      show the commands sent so far
+  !map          show the rooms seen so far in the dot format
+  !explore      pick up the safe items of every room
+  !crack        go to the checkpoint and try the items until the floor lets the droid in
+  !help         show this help
+";
+
+fn crack(game: &mut Game<Intcode>) {
+    match game.crack() {
+        Ok(text) => {
+            print!("{}", text);
+            if let Some(password) = adventure::password(&text) {
+                info!("password: {}", password);
+            }
+        },
+        Err(e) => error!("{}", e),
+    }
+}
+
+fn shell(game: &mut Game<Intcode>, line: &str) {
+    let words = line.split_whitespace().collect::<Vec<_>>();
+    match words.as_slice() {
+        ["!save", name] => game.save(name),
+        ["!load", name] => {
+            if !game.load(name) {
+                println!("No game saved as {}", name);
+            }
+        },
+        ["!history"] => {
+            for (n, command) in game.history().iter().enumerate() {
+                println!("{:>4}  {}", n + 1, command);
+            }
+        },
+        ["!map"] => print!("{}", game.map().dot()),
+        ["!explore"] => game.explore(),
+        ["!crack"] => crack(game),
+        _ => print!("{}", HELP),
+    }
+}
+
+fn main() {
+    let opt = Opt::from_args();
+    advent::init_logging();
+    let buffer = fs::read_to_string(opt.input).expect("Unable to read input file");
+    let mut game = Game::new(Intcode::new(buffer));
+    if opt.auto {
+        game.explore();
+        crack(&mut game);
+        if !game.halted() {
+            process::exit(1);
+        }
+        return;
+    }
+    print!("{}", game.start());
+    let stdin = io::stdin();
+    for line in stdin.lock().lines() {
+        let line = line.expect("Unable to read command");
+        if line.starts_with('!') {
+            shell(&mut game, &line);
+            println!("Command?");
+        } else {
+            print!("{}", game.command(&line));
+        }
+        io::stdout().flush().unwrap();
+        if game.halted() {
+            break;
+        }
+    }
+}
@@ -4,19 +4,31 @@
 //!   - Point
 //!   - Coordinates
 //!   - Wire
+//!
+//! `Coordinates` holds every cell walked by a wire. The `Segment` and `Piece` structs describe
+//! the same wires with one item per move instead, so that intersections are found by sweeping
+//! over the moves without materializing any cell.
+//!
+//! The extended wire language adds diagonal moves (`UL`, `UR`, `DL`, `DR`) and a third axis
+//! (`F`, `B`). Its wires are walked cell by cell in 3D with the `Point3`, `Move` and `Wire3`
+//! structs.
 
 use log::*;
-use regex::Regex;
 use std::cmp::Ordering;
 use std::cmp::PartialOrd;
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fmt;
+use std::fs;
 use std::hash::{Hash, Hasher};
 use std::io;
 use std::io::BufRead;
 use std::iter::FromIterator;
 use std::ops::Add;
 use std::ops::BitAnd;
+use std::path::PathBuf;
+use std::process;
+use std::str::FromStr;
+use structopt::StructOpt;
 
 /// A point in 2D
 #[derive(Clone, Debug, Copy)]
@@ -36,9 +48,15 @@ impl Point {
         self.x == 0 && self.y == 0
     }
 
+    /// Manhattan distance to the origin
     pub fn distance(&self) -> i32 {
         self.x.abs() + self.y.abs()
     }
+
+    /// Manhattan distance to another point
+    fn distance_to(&self, other: &Point) -> i32 {
+        (self.x - other.x).abs() + (self.y - other.y).abs()
+    }
 }
 
 impl PartialEq for Point {
@@ -59,9 +77,10 @@ impl PartialOrd for Point {
     }
 }
 
+/// Points are ordered by abscissa then ordinate, use a `Metric` to compare their distances
 impl Ord for Point {
     fn cmp(&self, other: &Point) -> Ordering {
-        self.distance().cmp(&other.distance())
+        (self.x, self.y).cmp(&(other.x, other.y))
     }
 }
 impl Hash for Point {
@@ -84,6 +103,135 @@ impl Add for Point {
     }
 }
 
+impl FromStr for Point {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut coordinates = s.split(',').map(|c| c.trim().parse::<i32>());
+        match (coordinates.next(), coordinates.next(), coordinates.next()) {
+            (Some(Ok(x)), Some(Ok(y)), None) => Ok(Point::new(x, y)),
+            _ => Err(format!("Invalid point: {} (expected x,y)", s)),
+        }
+    }
+}
+
+/// A way to measure how far the points are from a reference point, the origin unless anchored
+///
+/// Distances must not shrink when the horizontal or the vertical gap between the points grows,
+/// so that the closest cells of a horizontal or vertical line are a run around the projection
+/// of the anchor on it, `spread` giving how far that run goes.
+trait Metric {
+    /// Distance between two points
+    fn between(&self, a: &Point, b: &Point) -> i64;
+
+    /// Point the distances are measured from
+    fn anchor(&self) -> Point {
+        Point::new(0, 0)
+    }
+
+    /// Distance of a point to the anchor
+    fn measure(&self, p: &Point) -> i64 {
+        self.between(&self.anchor(), p)
+    }
+
+    /// How far the cells of a line `gap` away from the anchor stay as close as the projection
+    /// of the anchor on it
+    fn spread(&self, _gap: i32) -> i32 {
+        0
+    }
+}
+
+/// Sum of the horizontal and vertical gaps, the distance of the puzzle
+struct Manhattan;
+
+impl Metric for Manhattan {
+    fn between(&self, a: &Point, b: &Point) -> i64 {
+        i64::from(a.distance_to(b))
+    }
+}
+
+/// Largest of the horizontal and vertical gaps
+struct Chebyshev;
+
+impl Metric for Chebyshev {
+    fn between(&self, a: &Point, b: &Point) -> i64 {
+        i64::from((a.x - b.x).abs().max((a.y - b.y).abs()))
+    }
+
+    fn spread(&self, gap: i32) -> i32 {
+        gap
+    }
+}
+
+/// Square of the straight line distance, which orders the points like the distance itself
+struct EuclideanSquared;
+
+impl Metric for EuclideanSquared {
+    fn between(&self, a: &Point, b: &Point) -> i64 {
+        let (dx, dy) = (i64::from(a.x - b.x), i64::from(a.y - b.y));
+        dx * dx + dy * dy
+    }
+}
+
+/// Another metric measuring the distances from `anchor` instead of the origin
+struct Anchored {
+    anchor: Point,
+    metric: Box<dyn Metric>,
+}
+
+impl Metric for Anchored {
+    fn between(&self, a: &Point, b: &Point) -> i64 {
+        self.metric.between(a, b)
+    }
+
+    fn anchor(&self) -> Point {
+        self.anchor
+    }
+
+    fn spread(&self, gap: i32) -> i32 {
+        self.metric.spread(gap)
+    }
+}
+
+/// Name of a metric on the command line
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum MetricName {
+    Manhattan,
+    Chebyshev,
+    EuclideanSquared,
+}
+
+impl MetricName {
+    /// Returns the metric, measuring the distances from `anchor` when given
+    fn metric(self, anchor: Option<Point>) -> Box<dyn Metric> {
+        let metric: Box<dyn Metric> = match self {
+            MetricName::Manhattan => Box::new(Manhattan),
+            MetricName::Chebyshev => Box::new(Chebyshev),
+            MetricName::EuclideanSquared => Box::new(EuclideanSquared),
+        };
+        match anchor {
+            None => metric,
+            Some(anchor) => Box::new(Anchored { anchor, metric }),
+        }
+    }
+}
+
+impl FromStr for MetricName {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "manhattan" => Ok(MetricName::Manhattan),
+            "chebyshev" => Ok(MetricName::Chebyshev),
+            "euclidean-squared" => Ok(MetricName::EuclideanSquared),
+            _ => Err(format!(
+                "Invalid metric: {} (expected manhattan, chebyshev or euclidean-squared)",
+                s
+            )),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 struct Coordinates(Vec<Point>);
 
@@ -114,48 +262,6 @@ impl From<Coordinates> for HashSet<Point> {
     }
 }
 
-impl From<String> for Coordinates {
-    fn from(direction: String) -> Self {
-        let re = Regex::new(r"(L|R|U|D)([0-9]+)").unwrap();
-        let caps = re.captures(direction.as_str()).unwrap();
-        let orientation = caps.get(1).map_or("", |m| m.as_str());
-        let distance: i32 = caps
-            .get(2)
-            .expect("Invalid format")
-            .as_str()
-            .parse()
-            .expect("Invalid format");
-        match orientation {
-            "L" => {
-                debug!("{} {}", orientation, distance);
-                (1..distance + 1)
-                    .map(|i| Point { x: -i, y: 0 })
-                    .collect::<Coordinates>()
-                //)
-            },
-            "R" => {
-                debug!("{} {}", orientation, distance);
-                (1..distance + 1)
-                    .map(|i| Point { x: i, y: 0 })
-                    .collect::<Coordinates>()
-            },
-            "U" => {
-                debug!("{} {}", orientation, distance);
-                (1..distance + 1)
-                    .map(|i| Point { x: 0, y: i })
-                    .collect::<Coordinates>()
-            },
-            "D" => {
-                debug!("{} {}", orientation, distance);
-                (1..distance + 1)
-                    .map(|i| Point { x: 0, y: -i })
-                    .collect::<Coordinates>()
-            },
-            _ => panic!("Invalid orientation"),
-        }
-    }
-}
-
 impl<'a> Extend<&'a Point> for Coordinates {
     fn extend<T: IntoIterator<Item = &'a Point>>(&mut self, iter: T) {
         let default_origin = &Point::new(0, 0);
@@ -232,6 +338,22 @@ impl Wire {
         }
     }
 
+    /// Returns the wire walking the moves of the puzzle
+    fn walk(moves: &[Move]) -> Self {
+        let mut position = Point::new(0, 0);
+        let mut coordinates = Coordinates::new();
+        for m in moves {
+            for _ in 0..m.distance {
+                position = position + Point::new(m.step.x, m.step.y);
+                coordinates.push(position);
+            }
+        }
+        Wire {
+            path: moves.iter().map(|m| m.to_string()).collect(),
+            coordinates,
+        }
+    }
+
     fn step_to_intersection(&self, i: Point) -> usize {
         self.coordinates
             .iter()
@@ -264,21 +386,12 @@ impl FromIterator<Point> for Wire {
     }
 }
 
-impl FromIterator<String> for Wire {
-    fn from_iter<T>(iter: T) -> Self
-    where
-        T: IntoIterator<Item = String>,
-    {
-        let mut path = Vec::new();
-        let mut path_coordinates = Coordinates::new();
-        for v in iter {
-            path.push(v.clone());
-            path_coordinates.extend(Coordinates::from(v).iter());
-        }
-        Wire {
-            path,
-            coordinates: path_coordinates,
-        }
+/// Parses a wire of the puzzle, rejecting the moves of the extended language
+impl FromStr for Wire {
+    type Err = Vec<ParseError>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_classic_moves(s).map(|moves| Wire::walk(&moves))
     }
 }
 
@@ -301,27 +414,25 @@ fn common_points(wires: Vec<Wire>) -> Option<Coordinates> {
     }
 }
 
-fn closest_intersection_point(wires: Vec<Wire>) -> Option<Point> {
+/// Returns the intersection closest to the anchor of `metric`, the lowest point among the ties
+fn closest_intersection_point(wires: Vec<Wire>, metric: &dyn Metric) -> Option<Point> {
     let common = common_points(wires);
     info!("common points: {:?}", common);
     match common {
         None => None,
         Some(c) => c.iter().fold(None, |min, x| match min {
             None => Some(*x),
-            Some(y) => Some(if x < &y { *x } else { y }),
+            Some(y) => Some(if (metric.measure(x), *x) < (metric.measure(&y), y) {
+                *x
+            } else {
+                y
+            }),
         }),
     }
 }
 
-fn create_wire_from_string(s: String) -> Wire {
-    s.split(',')
-        .flat_map(|s| s.trim().parse::<String>().ok())
-        .collect::<Wire>()
-}
-
 fn minimal_steps_to_intersection(wires: Vec<Wire>) -> Option<usize> {
-    common_points(wires.clone())
-        .expect("no common points")
+    common_points(wires.clone())?
         .iter()
         .map(|p| {
             wires
@@ -331,75 +442,1344 @@ fn minimal_steps_to_intersection(wires: Vec<Wire>) -> Option<usize> {
         .min()
 }
 
+/// Field of the intersection report to sort it by
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SortKey {
+    X,
+    Y,
+    Distance,
+    /// Steps of the wire of this index, intersections missing the wire coming last
+    Wire(usize),
+    Total,
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "x" => Ok(SortKey::X),
+            "y" => Ok(SortKey::Y),
+            "distance" => Ok(SortKey::Distance),
+            "total" => Ok(SortKey::Total),
+            _ => s
+                .strip_prefix("wire")
+                .and_then(|n| n.parse::<usize>().ok())
+                .filter(|n| *n > 0)
+                .map(|n| SortKey::Wire(n - 1))
+                .ok_or_else(|| {
+                    format!(
+                        "Invalid sort key: {} (expected x, y, distance, total or wire<N>)",
+                        s
+                    )
+                }),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Csv,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("Invalid format: {} (expected csv or json)", s)),
+        }
+    }
+}
+
+/// A point shared by at least two wires
+#[derive(Clone, Debug, PartialEq)]
+struct Intersection {
+    point: Point,
+    /// Distance to the anchor of the metric of the report
+    distance: i64,
+    /// Steps of every wire to reach the point, `None` for the wires not going through it
+    steps: Vec<Option<usize>>,
+    total: usize,
+}
+
+/// Every intersection of a set of wires
+#[derive(Clone, Debug, PartialEq)]
+struct Report {
+    wires: usize,
+    intersections: Vec<Intersection>,
+}
+
+impl Report {
+    /// Lists the points shared by any two wires or more, closest to the anchor of `metric`
+    /// first
+    fn new(wires: &[Vec<Segment>], metric: &dyn Metric) -> Self {
+        let pieces = wires
+            .iter()
+            .map(|w| w.iter().map(Piece::from_segment).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        // a wire going several times through a point keeps the steps of its first visit
+        let mut steps: HashMap<Point, Vec<Option<usize>>> = HashMap::new();
+        for (a, b) in (0..wires.len()).flat_map(|a| (a + 1..wires.len()).map(move |b| (a, b))) {
+            for piece in crossings(&pieces[a], &pieces[b]) {
+                for point in piece.cells() {
+                    let visits = steps
+                        .entry(point)
+                        .or_insert_with(|| vec![None; wires.len()]);
+                    for (wire, walk) in [a, b].iter().zip(&piece.walks) {
+                        let reached = walk.steps_to(&point);
+                        visits[*wire] = Some(visits[*wire].map_or(reached, |s| s.min(reached)));
+                    }
+                }
+            }
+        }
+        let mut intersections = steps
+            .into_iter()
+            .map(|(point, steps)| Intersection {
+                point,
+                distance: metric.measure(&point),
+                total: steps.iter().flatten().sum(),
+                steps,
+            })
+            .collect::<Vec<_>>();
+        intersections.sort_by_key(|i| (i.distance, i.point));
+        Report {
+            wires: wires.len(),
+            intersections,
+        }
+    }
+
+    /// Sorts the intersections by `key`, keeping the current order of the equal ones
+    fn sort_by(&mut self, key: SortKey) {
+        match key {
+            SortKey::X => self.intersections.sort_by_key(|i| i.point.x),
+            SortKey::Y => self.intersections.sort_by_key(|i| i.point.y),
+            SortKey::Distance => self.intersections.sort_by_key(|i| i.distance),
+            SortKey::Total => self.intersections.sort_by_key(|i| i.total),
+            SortKey::Wire(n) => self
+                .intersections
+                .sort_by_key(|i| i.steps.get(n).cloned().flatten().unwrap_or(usize::MAX)),
+        }
+    }
+
+    fn to_csv(&self) -> String {
+        let mut header = vec!["x".to_string(), "y".to_string(), "distance".to_string()];
+        header.extend((1..=self.wires).map(|n| format!("wire{}", n)));
+        header.push("total".to_string());
+        let mut output = header.join(",") + "\n";
+        for i in &self.intersections {
+            let mut fields = vec![i.point.x.to_string(), i.point.y.to_string()];
+            fields.push(i.distance.to_string());
+            fields.extend(
+                i.steps
+                    .iter()
+                    .map(|s| s.map_or(String::new(), |s| s.to_string())),
+            );
+            fields.push(i.total.to_string());
+            output += &(fields.join(",") + "\n");
+        }
+        output
+    }
+
+    fn to_json(&self) -> String {
+        let intersections = self
+            .intersections
+            .iter()
+            .map(|i| {
+                let steps = i
+                    .steps
+                    .iter()
+                    .map(|s| s.map_or("null".to_string(), |s| s.to_string()))
+                    .collect::<Vec<_>>();
+                format!(
+                    "  {{\"x\": {}, \"y\": {}, \"distance\": {}, \"steps\": [{}], \"total\": {}}}",
+                    i.point.x,
+                    i.point.y,
+                    i.distance,
+                    steps.join(", "),
+                    i.total
+                )
+            })
+            .collect::<Vec<_>>();
+        if intersections.is_empty() {
+            "[]\n".to_string()
+        } else {
+            format!("[\n{}\n]\n", intersections.join(",\n"))
+        }
+    }
+
+    fn export(&self, format: Format) -> String {
+        match format {
+            Format::Csv => self.to_csv(),
+            Format::Json => self.to_json(),
+        }
+    }
+}
+
+/// One move of a wire: the cells from `start` excluded to `end` included, walked after `steps`
+/// steps
+#[derive(Clone, Debug, Copy, PartialEq)]
+struct Segment {
+    start: Point,
+    end: Point,
+    steps: usize,
+}
+
+impl Segment {
+    /// Steps taken by the wire to reach `p`, which must be on the segment
+    fn steps_to(&self, p: &Point) -> usize {
+        self.steps + self.start.distance_to(p) as usize
+    }
+}
+
+/// Returns the segments of a wire made of the moves of the puzzle
+fn segments(moves: &[Move]) -> Vec<Segment> {
+    let mut start = Point::new(0, 0);
+    let mut steps = 0;
+    let mut segments = Vec::new();
+    for m in moves.iter().filter(|m| m.distance > 0) {
+        let end = start + Point::new(m.step.x * m.distance, m.step.y * m.distance);
+        segments.push(Segment { start, end, steps });
+        start = end;
+        steps += m.distance as usize;
+    }
+    segments
+}
+
+/// Cells shared by several wires: a horizontal or vertical line from `from` to `to`, both
+/// included, `from` being the bottom or left end
+///
+/// `walks` gives, for every wire, the segment of the wire going through these cells.
+#[derive(Clone, Debug, PartialEq)]
+struct Piece {
+    from: Point,
+    to: Point,
+    walks: Vec<Segment>,
+}
+
+impl Piece {
+    fn new(a: Point, b: Point, walks: Vec<Segment>) -> Self {
+        Piece {
+            from: Point::new(a.x.min(b.x), a.y.min(b.y)),
+            to: Point::new(a.x.max(b.x), a.y.max(b.y)),
+            walks,
+        }
+    }
+
+    /// Returns the cells of a segment
+    fn from_segment(s: &Segment) -> Self {
+        let unit = Point::new(
+            (s.end.x - s.start.x).signum(),
+            (s.end.y - s.start.y).signum(),
+        );
+        Piece::new(s.start + unit, s.end, vec![*s])
+    }
+
+    /// Single cells count as horizontal
+    fn is_vertical(&self) -> bool {
+        self.from.x == self.to.x && self.from.y != self.to.y
+    }
+
+    /// Returns the cells among which the lowest of the closest to the anchor of `metric` is
+    ///
+    /// The closest cells are a run around the projection of the anchor on the piece, clamped
+    /// to the piece: the lowest is an end of the piece, the projection or the low end of the run.
+    fn candidates(&self, metric: &dyn Metric) -> Vec<Point> {
+        let anchor = metric.anchor();
+        let mut points = vec![self.from, self.to];
+        if self.is_vertical() {
+            let spread = metric.spread((self.from.x - anchor.x).abs());
+            for y in [anchor.y - spread, anchor.y, anchor.y + spread].iter() {
+                if self.from.y < *y && self.to.y > *y {
+                    points.push(Point::new(self.from.x, *y));
+                }
+            }
+        } else {
+            let spread = metric.spread((self.from.y - anchor.y).abs());
+            for x in [anchor.x - spread, anchor.x, anchor.x + spread].iter() {
+                if self.from.x < *x && self.to.x > *x {
+                    points.push(Point::new(*x, self.from.y));
+                }
+            }
+        }
+        points
+    }
+
+    /// Combined steps of the wires to reach `p`
+    fn steps_to(&self, p: &Point) -> usize {
+        self.walks.iter().map(|w| w.steps_to(p)).sum()
+    }
+
+    /// Returns every cell of the piece, from `from` to `to`
+    fn cells(&self) -> impl Iterator<Item = Point> {
+        let (from, to) = (self.from, self.to);
+        (from.x..=to.x).flat_map(move |x| (from.y..=to.y).map(move |y| Point::new(x, y)))
+    }
+}
+
+/// Returns the overlaps of pieces lying on the same lines, `line` giving the line of a piece
+/// and `range` its extent along it
+fn overlaps<L, R>(a: &[&Piece], b: &[&Piece], line: L, range: R) -> Vec<Piece>
+where
+    L: Fn(&Point) -> i32,
+    R: Fn(&Point) -> i32,
+{
+    let mut lines: HashMap<i32, Vec<&Piece>> = HashMap::new();
+    for q in b {
+        lines.entry(line(&q.from)).or_default().push(q);
+    }
+    let mut found = Vec::new();
+    for p in a {
+        for q in lines.get(&line(&p.from)).into_iter().flatten() {
+            let low = range(&p.from).max(range(&q.from));
+            let high = range(&p.to).min(range(&q.to));
+            if low <= high {
+                let (from, to) = if p.is_vertical() || q.is_vertical() {
+                    (Point::new(p.from.x, low), Point::new(p.from.x, high))
+                } else {
+                    (Point::new(low, p.from.y), Point::new(high, p.from.y))
+                };
+                let walks = p.walks.iter().chain(&q.walks).cloned().collect();
+                found.push(Piece::new(from, to, walks));
+            }
+        }
+    }
+    found
+}
+
+/// Returns the crossings of horizontal and vertical pieces by sweeping a vertical line from
+/// left to right, the walks of the pieces of the first wire coming first
+fn sweep(horizontal: &[&Piece], vertical: &[&Piece], horizontal_first: bool) -> Vec<Piece> {
+    // at the same abscissa, horizontal pieces are inserted before and removed after the
+    // vertical ones are checked
+    let mut events = Vec::new();
+    for (i, h) in horizontal.iter().enumerate() {
+        events.push((h.from.x, 0, i));
+        events.push((h.to.x, 2, i));
+    }
+    for (i, v) in vertical.iter().enumerate() {
+        events.push((v.from.x, 1, i));
+    }
+    events.sort();
+    let mut active = BTreeSet::new();
+    let mut found = Vec::new();
+    for (x, kind, i) in events {
+        match kind {
+            0 => {
+                active.insert((horizontal[i].from.y, i));
+            },
+            2 => {
+                active.remove(&(horizontal[i].from.y, i));
+            },
+            _ => {
+                let v = vertical[i];
+                for (y, j) in active.range((v.from.y, 0)..=(v.to.y, usize::MAX)) {
+                    let h = horizontal[*j];
+                    let walks = if horizontal_first {
+                        h.walks.iter().chain(&v.walks).cloned().collect()
+                    } else {
+                        v.walks.iter().chain(&h.walks).cloned().collect()
+                    };
+                    found.push(Piece::new(Point::new(x, *y), Point::new(x, *y), walks));
+                }
+            },
+        }
+    }
+    found
+}
+
+/// Returns the cells shared by the pieces of `a` and the ones of `b`
+fn crossings(a: &[Piece], b: &[Piece]) -> Vec<Piece> {
+    let (av, ah): (Vec<&Piece>, Vec<&Piece>) = a.iter().partition(|p| p.is_vertical());
+    let (bv, bh): (Vec<&Piece>, Vec<&Piece>) = b.iter().partition(|p| p.is_vertical());
+    let mut found = overlaps(&ah, &bh, |p| p.y, |p| p.x);
+    found.extend(overlaps(&av, &bv, |p| p.x, |p| p.y));
+    found.extend(sweep(&ah, &bv, true));
+    found.extend(sweep(&bh, &av, false));
+    found
+}
+
+/// Returns the cells shared by all the wires
+fn common_pieces(wires: &[Vec<Segment>]) -> Vec<Piece> {
+    let mut iter = wires
+        .iter()
+        .map(|w| w.iter().map(Piece::from_segment).collect::<Vec<_>>());
+    match iter.next() {
+        None => Vec::new(),
+        Some(first) => iter.fold(first, |shared, w| crossings(&shared, &w)),
+    }
+}
+
+/// Returns the crossing closest to the anchor of `metric`, the lowest point among the ties
+fn closest_crossing(wires: &[Vec<Segment>], metric: &dyn Metric) -> Option<Point> {
+    common_pieces(wires)
+        .iter()
+        .flat_map(|p| p.candidates(metric))
+        .min_by_key(|p| (metric.measure(p), *p))
+}
+
+/// Returns the crossing reached with the minimal combined steps, with these steps
+///
+/// Steps change linearly along a piece, so they are minimal on one of its ends.
+fn fewest_steps_crossing(wires: &[Vec<Segment>]) -> Option<(Point, usize)> {
+    common_pieces(wires)
+        .iter()
+        .flat_map(|p| {
+            vec![p.from, p.to]
+                .into_iter()
+                .map(move |c| (c, p.steps_to(&c)))
+        })
+        .min_by_key(|(c, steps)| (*steps, *c))
+}
+
+fn minimal_steps_to_crossing(wires: &[Vec<Segment>]) -> Option<usize> {
+    fewest_steps_crossing(wires).map(|(_, steps)| steps)
+}
+
+/// Colours of the wires in the SVG drawing, reused when there are more wires
+const COLOURS: [&str; 6] = [
+    "#e6194b", "#3cb44b", "#4363d8", "#f58231", "#911eb4", "#42d4f4",
+];
+
+/// Width in pixels of the SVG drawing, its height follows the aspect of the wires
+const SVG_WIDTH: f64 = 800.0;
+
+/// Draws the wires as SVG
+///
+/// The drawing uses the puzzle coordinates, with the ordinates flipped so that up is up, and
+/// its view box fits the wires whatever their size: strokes keep a width in pixels while
+/// markers and labels are sized relative to the extent of the wires. Every cell shared by two
+/// wires is circled, overlapping moves are drawn as thick lines, and the crossing closest to
+/// the anchor of `metric` and the one with the fewest steps are labelled.
+fn svg(wires: &[Vec<Segment>], metric: &dyn Metric) -> String {
+    let origin = Point::new(0, 0);
+    let vertices: Vec<Vec<Point>> = wires
+        .iter()
+        .map(|w| {
+            std::iter::once(origin)
+                .chain(w.iter().map(|s| s.end))
+                .collect()
+        })
+        .collect();
+    let all = || vertices.iter().flatten().chain(std::iter::once(&origin));
+    let (min_x, max_x) = (
+        all().map(|p| p.x).min().unwrap(),
+        all().map(|p| p.x).max().unwrap(),
+    );
+    let (min_y, max_y) = (
+        all().map(|p| p.y).min().unwrap(),
+        all().map(|p| p.y).max().unwrap(),
+    );
+    let extent = f64::from((max_x - min_x).max(max_y - min_y).max(1));
+    let margin = extent * 0.05;
+    let width = f64::from(max_x - min_x) + 2.0 * margin;
+    let height = f64::from(max_y - min_y) + 2.0 * margin;
+    let radius = extent * 0.01;
+    let font = extent * 0.03;
+
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{:.0}\" height=\"{:.0}\" \
+         viewBox=\"{:.2} {:.2} {:.2} {:.2}\">\n",
+        SVG_WIDTH,
+        SVG_WIDTH * height / width,
+        f64::from(min_x) - margin,
+        -f64::from(max_y) - margin,
+        width,
+        height
+    );
+    for (i, points) in vertices.iter().enumerate() {
+        let points = points
+            .iter()
+            .map(|p| format!("{},{}", p.x, -p.y))
+            .collect::<Vec<_>>()
+            .join(" ");
+        out += &format!(
+            "  <polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"1\" \
+             vector-effect=\"non-scaling-stroke\"/>\n",
+            points,
+            COLOURS[i % COLOURS.len()]
+        );
+    }
+    let pieces: Vec<Vec<Piece>> = wires
+        .iter()
+        .map(|w| w.iter().map(Piece::from_segment).collect())
+        .collect();
+    for (i, a) in pieces.iter().enumerate() {
+        for b in &pieces[i + 1..] {
+            for p in crossings(a, b) {
+                if p.from != p.to {
+                    out += &format!(
+                        "  <line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"black\" \
+                         stroke-width=\"4\" vector-effect=\"non-scaling-stroke\"/>\n",
+                        p.from.x, -p.from.y, p.to.x, -p.to.y
+                    );
+                }
+                out += &format!(
+                    "  <circle cx=\"{}\" cy=\"{}\" r=\"{:.2}\" fill=\"none\" stroke=\"black\" \
+                     vector-effect=\"non-scaling-stroke\"/>\n",
+                    p.from.x, -p.from.y, radius
+                );
+            }
+        }
+    }
+    out += &format!(
+        "  <circle cx=\"0\" cy=\"0\" r=\"{:.2}\" fill=\"black\"/>\n",
+        radius * 1.5
+    );
+    let labels = closest_crossing(wires, metric)
+        .map(|p| {
+            (
+                p,
+                format!("closest: {} at distance {}", p, metric.measure(&p)),
+            )
+        })
+        .into_iter()
+        .chain(
+            fewest_steps_crossing(wires)
+                .map(|(p, steps)| (p, format!("fewest steps: {} after {} steps", p, steps))),
+        );
+    for (p, label) in labels {
+        out += &format!(
+            "  <circle cx=\"{}\" cy=\"{}\" r=\"{:.2}\" fill=\"red\"/>\n  \
+             <text x=\"{:.2}\" y=\"{}\" font-size=\"{:.2}\">{}</text>\n",
+            p.x,
+            -p.y,
+            radius * 1.5,
+            f64::from(p.x) + radius * 2.0,
+            -p.y,
+            font,
+            label
+        );
+    }
+    out += "</svg>\n";
+    out
+}
+
+/// A point in 3D, for the wires of the extended language
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct Point3 {
+    x: i32,
+    y: i32,
+    z: i32,
+}
+
+impl Point3 {
+    fn new(x: i32, y: i32, z: i32) -> Self {
+        Point3 { x, y, z }
+    }
+
+    /// Manhattan distance to the origin
+    fn distance(&self) -> i32 {
+        self.x.abs() + self.y.abs() + self.z.abs()
+    }
+}
+
+impl fmt::Display for Point3 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(x: {}, y: {}, z: {})", self.x, self.y, self.z)
+    }
+}
+
+impl Add for Point3 {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Point3::new(self.x + other.x, self.y + other.y, self.z + other.z)
+    }
+}
+
+/// A move of the extended wire language: `distance` times the same step to a neighbour cell
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Move {
+    step: Point3,
+    distance: i32,
+}
+
+impl Move {
+    /// Returns true if the move belongs to the language of the puzzle
+    fn is_classic(&self) -> bool {
+        self.step.z == 0 && (self.step.x == 0 || self.step.y == 0)
+    }
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let vertical = match self.step.y {
+            1 => "U",
+            -1 => "D",
+            _ => "",
+        };
+        let horizontal = match self.step.x {
+            -1 => "L",
+            1 => "R",
+            _ => "",
+        };
+        let depth = match self.step.z {
+            1 => "F",
+            -1 => "B",
+            _ => "",
+        };
+        write!(f, "{}{}{}{}", vertical, horizontal, depth, self.distance)
+    }
+}
+
+impl FromStr for Move {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let split = s.find(|c: char| c.is_ascii_digit()).unwrap_or(s.len());
+        let (direction, distance) = s.split_at(split);
+        let step = match direction {
+            "L" => Point3::new(-1, 0, 0),
+            "R" => Point3::new(1, 0, 0),
+            "U" => Point3::new(0, 1, 0),
+            "D" => Point3::new(0, -1, 0),
+            "UL" => Point3::new(-1, 1, 0),
+            "UR" => Point3::new(1, 1, 0),
+            "DL" => Point3::new(-1, -1, 0),
+            "DR" => Point3::new(1, -1, 0),
+            "F" => Point3::new(0, 0, 1),
+            "B" => Point3::new(0, 0, -1),
+            _ => return Err("invalid orientation".to_string()),
+        };
+        let distance = distance
+            .parse()
+            .map_err(|_| "invalid distance".to_string())?;
+        Ok(Move { step, distance })
+    }
+}
+
+/// Invalid move of a wire definition, `wire` and `token` being indices starting at 0
+#[derive(Clone, Debug, PartialEq)]
+struct ParseError {
+    wire: usize,
+    token: usize,
+    text: String,
+    message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "wire {}, move {} ({:?}): {}",
+            self.wire + 1,
+            self.token + 1,
+            self.text,
+            self.message
+        )
+    }
+}
+
+/// Returns the moves of a wire, or an error for every invalid one
+///
+/// Errors are reported for the first wire, `parse_wires` fixing their index.
+fn parse_moves(s: &str) -> Result<Vec<Move>, Vec<ParseError>> {
+    let mut moves = Vec::new();
+    let mut errors = Vec::new();
+    for (token, text) in s.split(',').enumerate() {
+        match text.trim().parse() {
+            Ok(m) => moves.push(m),
+            Err(message) => errors.push(ParseError {
+                wire: 0,
+                token,
+                text: text.to_string(),
+                message,
+            }),
+        }
+    }
+    if errors.is_empty() {
+        Ok(moves)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Same as `parse_moves`, rejecting the moves of the extended language
+fn parse_classic_moves(s: &str) -> Result<Vec<Move>, Vec<ParseError>> {
+    let moves = parse_moves(s)?;
+    let errors = moves
+        .iter()
+        .zip(s.split(','))
+        .enumerate()
+        .filter(|(_, (m, _))| !m.is_classic())
+        .map(|(token, (_, text))| ParseError {
+            wire: 0,
+            token,
+            text: text.to_string(),
+            message: "only L, R, U and D are supported".to_string(),
+        })
+        .collect::<Vec<_>>();
+    if errors.is_empty() {
+        Ok(moves)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Returns the moves of every wire, or the errors of all the wires
+fn parse_wires(lines: &[String]) -> Result<Vec<Vec<Move>>, Vec<ParseError>> {
+    let mut wires = Vec::new();
+    let mut errors = Vec::new();
+    for (wire, line) in lines.iter().enumerate() {
+        match parse_moves(line) {
+            Ok(moves) => wires.push(moves),
+            Err(e) => errors.extend(e.into_iter().map(|e| ParseError { wire, ..e })),
+        }
+    }
+    if errors.is_empty() {
+        Ok(wires)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Cells walked by a wire of the extended language, with the steps taken to reach them first
+///
+/// A diagonal move steps to a corner neighbour in one step, so two diagonal wires crossing
+/// between the cells do not intersect.
+#[derive(Clone, Debug, Default)]
+struct Wire3(HashMap<Point3, usize>);
+
+impl Wire3 {
+    fn walk(moves: &[Move]) -> Self {
+        let mut cells = HashMap::new();
+        let mut position = Point3::default();
+        let mut steps = 0;
+        for m in moves {
+            for _ in 0..m.distance {
+                position = position + m.step;
+                steps += 1;
+                cells.entry(position).or_insert(steps);
+            }
+        }
+        Wire3(cells)
+    }
+}
+
+impl FromStr for Wire3 {
+    type Err = Vec<ParseError>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_moves(s).map(|moves| Wire3::walk(&moves))
+    }
+}
+
+/// Returns the cells walked by all the wires, with the combined steps to reach them
+fn intersections3(wires: &[Wire3]) -> Vec<(Point3, usize)> {
+    match wires.iter().min_by_key(|w| w.0.len()) {
+        None => Vec::new(),
+        Some(smallest) => smallest
+            .0
+            .keys()
+            .filter(|p| **p != Point3::default())
+            .filter_map(|p| {
+                wires
+                    .iter()
+                    .map(|w| w.0.get(p).copied())
+                    .sum::<Option<usize>>()
+                    .map(|steps| (*p, steps))
+            })
+            .collect(),
+    }
+}
+
+fn closest_intersection3(wires: &[Wire3]) -> Option<Point3> {
+    intersections3(wires)
+        .into_iter()
+        .map(|(p, _)| p)
+        .min_by_key(|p| (p.distance(), *p))
+}
+
+fn minimal_steps_to_intersection3(wires: &[Wire3]) -> Option<usize> {
+    intersections3(wires)
+        .into_iter()
+        .map(|(_, steps)| steps)
+        .min()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn create_segments_from_string(s: &str) -> Result<Vec<Segment>, Vec<ParseError>> {
+        parse_classic_moves(s).map(|moves| segments(&moves))
+    }
+
     #[test]
     fn test_basic_examples() {
         let wires: Vec<Wire> = vec![
-            create_wire_from_string("R75,D30,R83,U83,L12,D49,R71,U7,L72".to_string()),
-            create_wire_from_string("U62,R66,U55,R34,D71,R55,D58,R83".to_string()),
+            "R75,D30,R83,U83,L12,D49,R71,U7,L72"
+                .parse::<Wire>()
+                .unwrap(),
+            "U62,R66,U55,R34,D71,R55,D58,R83".parse::<Wire>().unwrap(),
         ];
         assert_eq!(
-            closest_intersection_point(wires)
+            closest_intersection_point(wires, &Manhattan)
                 .expect("Cannot find intersection")
                 .distance(),
             159
         );
         let wires: Vec<Wire> = vec![
-            create_wire_from_string("R98,U47,R26,D63,R33,U87,L62,D20,R33,U53,R51".to_string()),
-            create_wire_from_string("U98,R91,D20,R16,D67,R40,U7,R15,U6,R7".to_string()),
+            "R98,U47,R26,D63,R33,U87,L62,D20,R33,U53,R51"
+                .parse::<Wire>()
+                .unwrap(),
+            "U98,R91,D20,R16,D67,R40,U7,R15,U6,R7"
+                .parse::<Wire>()
+                .unwrap(),
         ];
         assert_eq!(
-            closest_intersection_point(wires)
+            closest_intersection_point(wires, &Manhattan)
                 .expect("Cannot find intersection")
                 .distance(),
             135
         );
     }
 
+    #[test]
+    fn test_metrics() {
+        let (a, b) = (Point::new(3, -4), Point::new(-1, 2));
+        assert_eq!(Manhattan.between(&a, &b), 10);
+        assert_eq!(Chebyshev.between(&a, &b), 6);
+        assert_eq!(EuclideanSquared.between(&a, &b), 52);
+        assert_eq!(EuclideanSquared.measure(&a), 25);
+        let anchored = MetricName::Chebyshev.metric(Some(b));
+        assert_eq!(anchored.measure(&a), 6);
+        assert_eq!(anchored.measure(&b), 0);
+
+        // points at the same distance are still different points
+        assert_eq!(Point::new(1, 0).cmp(&Point::new(0, 1)), Ordering::Greater);
+        assert_eq!(Point::new(0, 1).cmp(&Point::new(0, 1)), Ordering::Equal);
+
+        // (3, 3) is also the closest with Chebyshev, (6, 5) is the closest to (7, 5)
+        let wires = || {
+            vec![
+                "R8,U5,L5,D3".parse::<Wire>().unwrap(),
+                "U7,R6,D4,L4".parse::<Wire>().unwrap(),
+            ]
+        };
+        assert_eq!(
+            closest_intersection_point(wires(), &Chebyshev),
+            Some(Point::new(3, 3))
+        );
+        let anchor = Some(Point::new(7, 5));
+        assert_eq!(
+            closest_intersection_point(wires(), MetricName::Manhattan.metric(anchor).as_ref()),
+            Some(Point::new(6, 5))
+        );
+        assert_eq!(
+            "euclidean-squared".parse(),
+            Ok(MetricName::EuclideanSquared)
+        );
+        assert_eq!("7, 5".parse(), Ok(Point::new(7, 5)));
+        assert!("7".parse::<Point>().is_err());
+    }
+
     #[test]
     fn test_step_count() {
-        let w1 = create_wire_from_string("R8,U5,L5,D3".to_string());
+        let w1 = "R8,U5,L5,D3".parse::<Wire>().unwrap();
         assert_eq!(w1.step_to_intersection(Point::new(3, 0)), 3);
         assert_eq!(w1.step_to_intersection(Point::new(3, 3)), 20);
     }
     #[test]
     fn test_minimal_steps_to_intersection() {
         let wires: Vec<Wire> = vec![
-            create_wire_from_string("R8,U5,L5,D3".to_string()),
-            create_wire_from_string("U7,R6,D4,L4".to_string()),
+            "R8,U5,L5,D3".parse::<Wire>().unwrap(),
+            "U7,R6,D4,L4".parse::<Wire>().unwrap(),
         ];
         assert_eq!(minimal_steps_to_intersection(wires), Some(30));
 
         let wires: Vec<Wire> = vec![
-            create_wire_from_string("R75,D30,R83,U83,L12,D49,R71,U7,L72".to_string()),
-            create_wire_from_string("U62,R66,U55,R34,D71,R55,D58,R83".to_string()),
+            "R75,D30,R83,U83,L12,D49,R71,U7,L72"
+                .parse::<Wire>()
+                .unwrap(),
+            "U62,R66,U55,R34,D71,R55,D58,R83".parse::<Wire>().unwrap(),
         ];
         assert_eq!(minimal_steps_to_intersection(wires), Some(610));
 
         let wires: Vec<Wire> = vec![
-            create_wire_from_string("R98,U47,R26,D63,R33,U87,L62,D20,R33,U53,R51".to_string()),
-            create_wire_from_string("U98,R91,D20,R16,D67,R40,U7,R15,U6,R7".to_string()),
+            "R98,U47,R26,D63,R33,U87,L62,D20,R33,U53,R51"
+                .parse::<Wire>()
+                .unwrap(),
+            "U98,R91,D20,R16,D67,R40,U7,R15,U6,R7"
+                .parse::<Wire>()
+                .unwrap(),
         ];
         assert_eq!(minimal_steps_to_intersection(wires), Some(410));
+        let wires = vec!["R2".parse::<Wire>().unwrap(), "L2".parse().unwrap()];
+        assert_eq!(minimal_steps_to_intersection(wires), None);
+        assert_eq!(minimal_steps_to_intersection(Vec::new()), None);
+    }
+
+    #[test]
+    fn test_segments() {
+        let examples = vec![
+            ("R8,U5,L5,D3", "U7,R6,D4,L4", 6, 30),
+            (
+                "R75,D30,R83,U83,L12,D49,R71,U7,L72",
+                "U62,R66,U55,R34,D71,R55,D58,R83",
+                159,
+                610,
+            ),
+            (
+                "R98,U47,R26,D63,R33,U87,L62,D20,R33,U53,R51",
+                "U98,R91,D20,R16,D67,R40,U7,R15,U6,R7",
+                135,
+                410,
+            ),
+        ];
+        for (a, b, distance, steps) in examples {
+            let wires = vec![
+                create_segments_from_string(a).unwrap(),
+                create_segments_from_string(b).unwrap(),
+            ];
+            assert_eq!(
+                closest_crossing(&wires, &Manhattan).map(|p| p.distance()),
+                Some(distance)
+            );
+            assert_eq!(minimal_steps_to_crossing(&wires), Some(steps));
+        }
+    }
+
+    #[test]
+    fn test_segments_match_cells() {
+        let examples = vec![
+            // overlapping in the same direction, then in opposite directions
+            vec!["R8,U5", "R10"],
+            vec!["L3,U2,R9,D4", "R6,U2,L8"],
+            // a wire crossing itself, and three wires
+            vec!["R5,U2,L2,D4,L6", "D2,R8"],
+            vec!["R8,U5,L5,D3", "U7,R6,D4,L4", "U3,R8"],
+            // runs of cells tied with the chebyshev metric
+            vec!["R5,U3,L10", "L5,U3,R10"],
+            vec!["U5,R3,D10", "D5,R3,U10"],
+        ];
+        for example in examples {
+            let cells = example
+                .iter()
+                .map(|w| w.parse::<Wire>().unwrap())
+                .collect::<Vec<_>>();
+            let segments = example
+                .iter()
+                .map(|w| create_segments_from_string(w).unwrap())
+                .collect::<Vec<_>>();
+            let metrics: Vec<Box<dyn Metric>> = vec![
+                Box::new(Manhattan),
+                Box::new(Chebyshev),
+                Box::new(EuclideanSquared),
+                MetricName::Manhattan.metric(Some(Point::new(5, 1))),
+                MetricName::EuclideanSquared.metric(Some(Point::new(-2, 4))),
+                MetricName::Chebyshev.metric(Some(Point::new(1, -1))),
+            ];
+            for metric in metrics {
+                assert_eq!(
+                    closest_crossing(&segments, metric.as_ref()),
+                    closest_intersection_point(cells.clone(), metric.as_ref()),
+                    "{:?}",
+                    example
+                );
+            }
+            assert_eq!(
+                minimal_steps_to_crossing(&segments),
+                minimal_steps_to_intersection(cells),
+                "{:?}",
+                example
+            );
+        }
+    }
+
+    #[test]
+    fn test_extended() {
+        assert_eq!(
+            "UL12".parse(),
+            Ok(Move {
+                step: Point3::new(-1, 1, 0),
+                distance: 12
+            })
+        );
+        assert_eq!(
+            "B3".parse::<Move>().map(|m| m.step),
+            Ok(Point3::new(0, 0, -1))
+        );
+        assert!("X3".parse::<Move>().is_err());
+        assert!("F".parse::<Move>().is_err());
+        assert!(
+            parse_moves("R8,U5, L5,D3")
+                .unwrap()
+                .iter()
+                .all(Move::is_classic)
+        );
+        assert!(!parse_moves("R8,UR5").unwrap().iter().all(Move::is_classic));
+        assert!(!parse_moves("F1").unwrap().iter().all(Move::is_classic));
+
+        // the classic examples give the same answers in 3D
+        let examples = vec![
+            ("R8,U5,L5,D3", "U7,R6,D4,L4", 6, 30),
+            (
+                "R75,D30,R83,U83,L12,D49,R71,U7,L72",
+                "U62,R66,U55,R34,D71,R55,D58,R83",
+                159,
+                610,
+            ),
+        ];
+        for (a, b, distance, steps) in examples {
+            let wires: Vec<Wire3> = vec![a.parse().unwrap(), b.parse().unwrap()];
+            assert_eq!(
+                closest_intersection3(&wires).map(|p| p.distance()),
+                Some(distance)
+            );
+            assert_eq!(minimal_steps_to_intersection3(&wires), Some(steps));
+        }
+
+        // diagonals crossing between (1, 1), (2, 1), (1, 2) and (2, 2) do not meet
+        let wires: Vec<Wire3> = vec!["UR4".parse().unwrap(), "R3,UL3".parse().unwrap()];
+        assert!(intersections3(&wires).is_empty());
+        let wires: Vec<Wire3> = vec!["UR4".parse().unwrap(), "R4,UL2".parse().unwrap()];
+        assert_eq!(intersections3(&wires), vec![(Point3::new(2, 2, 0), 2 + 6)]);
+        // a wire climbing along the third axis meets the other above the plane
+        let wires: Vec<Wire3> = vec!["F2,R3,U1".parse().unwrap(), "R2,F2,R3,B4".parse().unwrap()];
+        assert_eq!(closest_intersection3(&wires), Some(Point3::new(2, 0, 2)));
+        assert_eq!(minimal_steps_to_intersection3(&wires), Some(8));
+        assert_eq!(
+            closest_intersection3(&["R1,F1".parse().unwrap(), "F1,R1".parse().unwrap()]),
+            Some(Point3::new(1, 0, 1))
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let lines = vec![
+            "R8,U5,L5,D3".to_string(),
+            "U7,X6,D4,L4x".to_string(),
+            "R2,,R8 U5".to_string(),
+        ];
+        let errors = parse_wires(&lines).unwrap_err();
+        let found = errors
+            .iter()
+            .map(|e| (e.wire, e.token, e.text.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            vec![(1, 1, "X6"), (1, 3, "L4x"), (2, 1, ""), (2, 2, "R8 U5")]
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "wire 2, move 2 (\"X6\"): invalid orientation"
+        );
+        assert_eq!(errors[1].message, "invalid distance");
+        assert_eq!(parse_wires(&lines[..1]).map(|w| w[0].len()), Ok(4));
+
+        let errors = "R8,UR5,F2".parse::<Wire>().unwrap_err();
+        assert_eq!(
+            errors.iter().map(|e| e.token).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert_eq!(
+            "R8,U5,L5,D3"
+                .parse::<Wire>()
+                .map(|w| w.step_to_intersection(Point::new(3, 3))),
+            Ok(20)
+        );
+        assert!("R8,U5,L5,X3".parse::<Wire3>().is_err());
+        assert_eq!(
+            "UL12".parse::<Move>().map(|m| m.to_string()),
+            Ok("UL12".to_string())
+        );
+        assert_eq!(
+            "B3".parse::<Move>().map(|m| m.to_string()),
+            Ok("B3".to_string())
+        );
+        let wire = " R8, U5 ".parse::<Wire>().unwrap();
+        assert_eq!(wire.to_string(), "([\"R8\", \"U5\"])");
+        assert!("R8x,U5".parse::<Wire>().is_err());
+
+        let errors = create_segments_from_string("R8x,U5,UR2").unwrap_err();
+        assert_eq!(
+            errors
+                .iter()
+                .map(|e| (e.token, e.message.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(0, "invalid distance")]
+        );
+        let errors = create_segments_from_string("R8,U5,UR2").unwrap_err();
+        assert_eq!(
+            errors[0].to_string(),
+            "wire 1, move 3 (\"UR2\"): only L, R, U and D are supported"
+        );
+        assert_eq!(
+            create_segments_from_string("R8,U0,L5").map(|s| s.len()),
+            Ok(2)
+        );
+    }
+
+    #[test]
+    fn test_report() {
+        let wires = vec![
+            create_segments_from_string("R8,U5,L5,D3").unwrap(),
+            create_segments_from_string("U7,R6,D4,L4").unwrap(),
+            create_segments_from_string("L1,U3,R3").unwrap(),
+        ];
+        let mut report = Report::new(&wires, &Manhattan);
+        let points = report
+            .intersections
+            .iter()
+            .map(|i| i.point)
+            .collect::<Vec<_>>();
+        // (0, 3) and (2, 3) are only shared by the second and the third wires
+        assert_eq!(
+            points,
+            vec![
+                Point::new(0, 3),
+                Point::new(2, 3),
+                Point::new(3, 3),
+                Point::new(6, 5)
+            ]
+        );
+        assert_eq!(report.intersections[0].steps, vec![None, Some(3), Some(5)]);
+        assert_eq!(
+            report.intersections[2].steps,
+            vec![Some(20), Some(20), None]
+        );
+        assert_eq!(report.intersections[2].total, 40);
+
+        report.sort_by(SortKey::Total);
+        assert_eq!(report.intersections[0].point, Point::new(0, 3));
+        assert_eq!(report.intersections[1].total, 28);
+        report.sort_by(SortKey::Wire(0));
+        assert_eq!(report.intersections[0].point, Point::new(6, 5));
+        assert_eq!(report.intersections[3].steps[0], None);
+        report.sort_by(SortKey::Y);
+        assert_eq!(report.intersections[3].point, Point::new(6, 5));
+
+        let anchored = MetricName::Manhattan.metric(Some(Point::new(6, 5)));
+        let report = Report::new(&wires, anchored.as_ref());
+        assert_eq!(
+            report
+                .intersections
+                .iter()
+                .map(|i| (i.point, i.distance))
+                .collect::<Vec<_>>(),
+            vec![
+                (Point::new(6, 5), 0),
+                (Point::new(3, 3), 5),
+                (Point::new(2, 3), 6),
+                (Point::new(0, 3), 8)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_report_export() {
+        let wires = vec![
+            create_segments_from_string("R8,U5,L5,D3").unwrap(),
+            create_segments_from_string("U7,R6,D4,L4").unwrap(),
+            create_segments_from_string("L1,U3,R1").unwrap(),
+        ];
+        let report = Report::new(&wires, &Manhattan);
+        assert_eq!(
+            report.export(Format::Csv),
+            "x,y,distance,wire1,wire2,wire3,total\n0,3,3,,3,5,8\n3,3,6,20,20,,40\n\
+             6,5,11,15,15,,30\n"
+        );
+        assert_eq!(
+            report.export(Format::Json),
+            "[\n  {\"x\": 0, \"y\": 3, \"distance\": 3, \"steps\": [null, 3, 5], \"total\": 8},\n  \
+             {\"x\": 3, \"y\": 3, \"distance\": 6, \"steps\": [20, 20, null], \"total\": 40},\n  \
+             {\"x\": 6, \"y\": 5, \"distance\": 11, \"steps\": [15, 15, null], \"total\": 30}\n]\n"
+        );
+        assert_eq!(
+            Report::new(&wires[..1], &Manhattan).export(Format::Json),
+            "[]\n"
+        );
+        assert_eq!("wire2".parse(), Ok(SortKey::Wire(1)));
+        assert!("wire0".parse::<SortKey>().is_err());
+    }
+
+    #[test]
+    fn test_conflicting_options() {
+        let conflict = |args: &[&str], extended| {
+            Opt::from_iter(std::iter::once("day3").chain(args.iter().cloned())).conflict(extended)
+        };
+        assert_eq!(conflict(&["--cells", "--metric", "chebyshev"], false), None);
+        assert_eq!(
+            conflict(&["--report", "csv", "--sort", "total"], false),
+            None
+        );
+        assert!(conflict(&["--cells", "--svg", "wires.svg"], false).is_some());
+        assert!(conflict(&["--cells", "--report", "csv"], false).is_some());
+        assert!(conflict(&["--report", "csv", "--svg", "wires.svg"], false).is_some());
+        assert!(conflict(&["--sort", "total"], false).is_some());
+        assert_eq!(conflict(&["--cells"], true), None);
+        assert!(conflict(&["--report", "json"], true).is_some());
+        assert!(conflict(&["--metric", "chebyshev"], true).is_some());
+        assert!(conflict(&["--anchor", "1,2"], true).is_some());
+    }
+
+    #[test]
+    fn test_svg() {
+        let wires = vec![
+            create_segments_from_string("R8,U5,L5,D3").unwrap(),
+            create_segments_from_string("U7,R6,D4,L4").unwrap(),
+        ];
+        let drawing = svg(&wires, &Manhattan);
+        assert!(drawing.starts_with(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"800\" height=\"709\" \
+             viewBox=\"-0.40 -7.40 8.80 7.80\">"
+        ));
+        assert!(drawing.contains(
+            "<polyline points=\"0,0 8,0 8,-5 3,-5 3,-2\" fill=\"none\" \
+             stroke=\"#e6194b\""
+        ));
+        assert!(drawing.contains(
+            "<polyline points=\"0,0 0,-7 6,-7 6,-3 2,-3\" fill=\"none\" \
+             stroke=\"#3cb44b\""
+        ));
+        assert_eq!(drawing.matches("fill=\"none\" stroke=\"black\"").count(), 2);
+        assert!(drawing.contains(">closest: (x: 3, y: 3) at distance 6</text>"));
+        assert!(drawing.contains(">fewest steps: (x: 6, y: 5) after 30 steps</text>"));
+        assert!(drawing.ends_with("</svg>\n"));
+        let anchored = MetricName::Manhattan.metric(Some(Point::new(6, 5)));
+        let drawing = svg(&wires, anchored.as_ref());
+        assert!(drawing.contains(">closest: (x: 6, y: 5) at distance 0</text>"));
+
+        // markers follow the extent of the wires
+        let wires = vec![
+            create_segments_from_string("R100000,U50000").unwrap(),
+            create_segments_from_string("U50000,R100000").unwrap(),
+        ];
+        let drawing = svg(&wires, &Manhattan);
+        assert!(drawing.contains("viewBox=\"-5000.00 -55000.00 110000.00 60000.00\""));
+        assert!(drawing.contains("<circle cx=\"0\" cy=\"0\" r=\"1500.00\" fill=\"black\"/>"));
+    }
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "day3", about = "Advent of Code - Day 3")]
+struct Opt {
+    /// Compute the intersections cell by cell instead of sweeping over the segments
+    #[structopt(short, long)]
+    cells: bool,
+
+    /// Print every intersection in this format: csv or json
+    #[structopt(short, long)]
+    report: Option<Format>,
+
+    /// Field to sort the report by: x, y, distance, total or wire<N> [default: distance]
+    #[structopt(short, long)]
+    sort: Option<SortKey>,
+
+    /// Metric of the closest intersection: manhattan, chebyshev or euclidean-squared
+    #[structopt(short, long, default_value = "manhattan")]
+    metric: MetricName,
+
+    /// Measure the distances from this point, given as x,y, instead of the origin
+    #[structopt(short, long)]
+    anchor: Option<Point>,
+
+    /// Draw the wires and their intersections to this SVG file
+    #[structopt(long, parse(from_os_str))]
+    svg: Option<PathBuf>,
+}
+
+impl Opt {
+    /// Returns why the options do not apply together, `extended` telling if the wires use the
+    /// moves of the extended language
+    fn conflict(&self, extended: bool) -> Option<&'static str> {
+        if self.cells && (self.report.is_some() || self.svg.is_some()) {
+            Some("--cells cannot be used with reports and drawings")
+        } else if self.report.is_some() && self.svg.is_some() {
+            Some("--report and --svg cannot be used together")
+        } else if self.sort.is_some() && self.report.is_none() {
+            Some("--sort only applies to reports")
+        } else if extended && (self.report.is_some() || self.svg.is_some()) {
+            Some("reports and drawings only support the moves L, R, U and D")
+        } else if extended && (self.metric != MetricName::Manhattan || self.anchor.is_some()) {
+            Some("--metric and --anchor only support the moves L, R, U and D")
+        } else {
+            None
+        }
     }
 }
 
 fn main() {
+    let opt = Opt::from_args();
     advent::init_logging();
-    let wires: Vec<Wire> = io::stdin()
+    let metric = opt.metric.metric(opt.anchor);
+    let lines = match io::stdin()
         .lock()
         .lines()
-        .filter_map(|l| l.ok())
-        .map(|line| create_wire_from_string(line))
-        .collect();
-    match closest_intersection_point(wires.clone()) {
+        .collect::<Result<Vec<String>, _>>()
+    {
+        Ok(lines) => lines,
+        Err(e) => {
+            error!("Unable to read the wires: {}", e);
+            process::exit(1);
+        },
+    };
+    let moves = match parse_wires(&lines) {
+        Ok(moves) => moves,
+        Err(errors) => {
+            for e in errors {
+                error!("{}", e);
+            }
+            process::exit(1);
+        },
+    };
+    let extended = !moves.iter().flatten().all(Move::is_classic);
+    if let Some(conflict) = opt.conflict(extended) {
+        error!("{}", conflict);
+        process::exit(1);
+    }
+    if extended {
+        let wires: Vec<Wire3> = moves.iter().map(|m| Wire3::walk(m)).collect();
+        match closest_intersection3(&wires) {
+            None => info!("no intersection"),
+            Some(c) => {
+                info!("shortest intersection: {}", c);
+                info!("distance: {}", c.distance());
+            },
+        }
+        info!(
+            "minimal steps to intersection: {:?}",
+            minimal_steps_to_intersection3(&wires)
+        );
+        return;
+    }
+    if let Some(format) = opt.report {
+        let wires: Vec<Vec<Segment>> = moves.iter().map(|m| segments(m)).collect();
+        let mut report = Report::new(&wires, metric.as_ref());
+        report.sort_by(opt.sort.unwrap_or(SortKey::Distance));
+        print!("{}", report.export(format));
+        return;
+    }
+    if !opt.cells {
+        let wires: Vec<Vec<Segment>> = moves.iter().map(|m| segments(m)).collect();
+        if let Some(path) = opt.svg {
+            fs::write(path, svg(&wires, metric.as_ref())).expect("Unable to write SVG file");
+            return;
+        }
+        match closest_crossing(&wires, metric.as_ref()) {
+            None => info!("no intersection"),
+            Some(c) => {
+                info!("shortest intersection: {}", c);
+                info!("distance: {}", metric.measure(&c));
+            },
+        }
+        info!(
+            "minimal steps to intersection: {:?}",
+            minimal_steps_to_crossing(&wires)
+        );
+        return;
+    }
+    let wires: Vec<Wire> = moves.iter().map(|m| Wire::walk(m)).collect();
+    match closest_intersection_point(wires.clone(), metric.as_ref()) {
         None => info!("no intersection"),
         Some(c) => {
             info!("shortest intersection: {}", c);
-            info!("distance: {}", c.distance());
+            info!("distance: {}", metric.measure(&c));
         },
     }
     // Part two
@@ -1,266 +1,43 @@
+use advent::intcode::Intcode;
 use log::*;
 use std::fs;
 use std::io::{self, BufRead, Write};
 use std::path::PathBuf;
 use structopt::StructOpt;
 
-#[derive(Debug, Clone)]
-struct Intcode {
-    program: Vec<i64>,
-    cursor: i64,
-    relative_base: i64,
-}
-
-#[derive(Debug, Clone, PartialEq)]
-enum InstructionType {
-    Mul,
-    Add,
-    Input,
-    Output,
-    JumpIfTrue,
-    JumpIfFalse,
-    LessThan,
-    Equals,
-    Stop,
-    Adjustbase,
-}
-
-#[derive(Debug, Clone, PartialEq)]
-enum ParameterMode {
-    Position,
-    Immediate,
-    Relative,
-}
-
-struct Instruction {
-    pub instruction_type: InstructionType,
-    pub parameter_mode: Vec<ParameterMode>,
-}
-
-impl Instruction {
-    fn new(code: i64) -> Self {
-        let code_as_str = format!("{}", code);
-        let mut code_as_vec: Vec<i64> = code_as_str
-            .into_bytes()
-            .into_iter()
-            .map(|b| b as i64 - 48)
-            .collect::<Vec<i64>>();
-        debug!("code vec: {:?}", code_as_vec);
-        let (mut inst_type, size) = match code_as_vec.pop() {
-            Some(1) => (InstructionType::Add, 3),
-            Some(2) => (InstructionType::Mul, 3),
-            Some(3) => (InstructionType::Input, 1),
-            Some(4) => (InstructionType::Output, 1),
-            Some(5) => (InstructionType::JumpIfTrue, 2),
-            Some(6) => (InstructionType::JumpIfFalse, 2),
-            Some(7) => (InstructionType::LessThan, 3),
-            Some(8) => (InstructionType::Equals, 3),
-            Some(9) => (InstructionType::Adjustbase, 1),
-            _ => panic!("Unknown instruction"),
-        };
-        if let Some(x) = code_as_vec.pop() {
-            if x == 9 {
-                inst_type = InstructionType::Stop;
-            }
-        }
-        let mut parameter_mode = std::iter::repeat(ParameterMode::Position)
-            .take(size)
-            .collect::<Vec<_>>();
-        code_as_vec.reverse();
-        code_as_vec
-            .iter()
-            .enumerate()
-            .map(|(i, v)| {
-                debug!("getting: pos {} value {} size {}", i, v, size);
-                if *v == 1 {
-                    parameter_mode[i] = ParameterMode::Immediate;
-                } else if *v == 2 {
-                    parameter_mode[i] = ParameterMode::Relative;
-                }
-                debug! {"done"}
-            })
-            .for_each(drop);
-        Self {
-            instruction_type: inst_type,
-            parameter_mode,
-        }
-    }
-}
-
-impl Intcode {
-    fn new(code: String) -> Self {
-        Self {
-            program: code.split(",").map(|s| s.parse::<i64>().unwrap()).collect(),
-            cursor: 0,
-            relative_base: 0,
-        }
-    }
-
-    fn get(&mut self, pam: &ParameterMode) -> i64 {
-        trace!("getting {:?}", pam);
-        debug!("getting: {}", self.cursor);
-        let value = self.next().expect("Unable to get value") as usize;
-        debug!("got: {}", value);
-        match pam {
-            ParameterMode::Position => {
-                if value > self.program.len() {
-                    0
-                } else {
-                    self.program[value]
-                }
-            },
-            ParameterMode::Immediate => value as i64,
-            ParameterMode::Relative => {
-                let relative_value = value.wrapping_add(self.relative_base as usize);
-                if relative_value > self.program.len() {
-                    0
-                } else {
-                    self.program[relative_value]
-                }
-            },
-        }
-    }
-
-    fn size(&self) -> usize {
-        self.program.len()
-    }
-
-    fn set(&mut self, value: i64, pam: &ParameterMode) -> () {
-        let mut pos = self.next().expect("Unable to get position to set") as usize;
-        trace!("storing {} in {}", value, pos);
-        if *pam == ParameterMode::Relative {
-            pos = pos.wrapping_add(self.relative_base as usize);
-        }
-        if pos >= self.program.len() {
-            self.program.resize(pos + 1, 0);
-        }
-        self.program[pos] = value
-    }
-
-    fn run<R, W>(&mut self, mut reader: R, mut writer: W) -> ()
-    where
-        R: BufRead,
-        W: Write,
-    {
-        loop {
-            let next_instruction = self.next();
-            if next_instruction == None {
-                break;
-            }
-            debug!(
-                "opcode {:?} - index: {}",
-                next_instruction.unwrap() % 1000,
-                self.cursor - 1
-            );
-            let op = Instruction::new(next_instruction.expect("Missing next Instruction"));
-            let mut pm = op.parameter_mode.iter();
-            match op.instruction_type {
-                InstructionType::Stop => break,
-                InstructionType::Add => {
-                    let result = self.get(pm.next().expect("Missing operand"))
-                        + self.get(pm.next().expect("missing operand"));
-                    debug!("result for add: {}", result);
-                    self.set(result, pm.next().expect("missing operand"));
-                    false
-                },
-                InstructionType::Mul => {
-                    let result = self
-                        .get(pm.next().expect("Missing operand"))
-                        .wrapping_mul(self.get(pm.next().expect("missing operand")));
-                    self.set(result, pm.next().expect("missing operand"));
-                    false
-                },
-                InstructionType::Output => {
-                    let value = self.get(pm.next().expect("Missing operand"));
-                    info!("OUTPUT value: {:?}", value);
-                    write!(&mut writer, "OUTPUT value: {}\n", value).expect("Unable to write");
-                    false
-                },
-                InstructionType::Input => {
-                    let mut input = String::new();
-                    reader
-                        .read_line(&mut input)
-                        .expect("Unable to read user input");
-                    debug!("input: {}", input);
-                    let result = input
-                        .trim_end()
-                        .parse()
-                        .expect("cannot parse integer from input");
-                    self.set(result, pm.next().expect("missing operand"));
-                    false
-                },
-                InstructionType::JumpIfTrue => {
-                    let param1 = self.get(pm.next().expect("Missing operand"));
-                    if param1 != 0 {
-                        let param2 = self.get(pm.next().expect("Missing operand")); // self.next().expect("Unable to get value");
-                        debug!("changing cursor to {}", param2);
-                        self.cursor = param2;
-                    } else {
-                        self.next();
-                    }
-                    false
-                },
-                InstructionType::JumpIfFalse => {
-                    let param1 = self.get(pm.next().expect("Missing operand"));
-                    if param1 == 0 {
-                        let param2 = self.get(pm.next().expect("Missing operand")); // self.next().expect("Unable to get value");
-                        debug!("changing cursor to {}", param2);
-                        self.cursor = param2;
-                    } else {
-                        self.next();
-                    }
-                    false
-                },
-                InstructionType::LessThan => {
-                    let param1 = self.get(pm.next().expect("Missing operand"));
-                    let param2 = self.get(pm.next().expect("Missing operand"));
-                    if param1 < param2 {
-                        self.set(1, pm.next().expect("missing operand"));
-                    } else {
-                        self.set(0, pm.next().expect("missing operand"));
-                    }
-                    false
-                },
-                InstructionType::Equals => {
-                    let param1 = self.get(pm.next().expect("Missing operand"));
-                    let param2 = self.get(pm.next().expect("Missing operand"));
-                    if param1 == param2 {
-                        self.set(1, pm.next().expect("missing operand"));
-                    } else {
-                        self.set(0, pm.next().expect("missing operand"));
-                    }
-                    false
-                },
-                InstructionType::Adjustbase => {
-                    let value = self.get(pm.next().expect("Missing operand"));
-                    info!("Ajust base value: {:?}", value);
-                    self.relative_base = self.relative_base + value;
-                    false
-                },
-            };
-        }
-    }
-}
-
-impl Iterator for Intcode {
-    type Item = i64;
-
-    // just return the str reference
-    fn next(&mut self) -> Option<Self::Item> {
-        debug!("getting next: {} on {:?}", self.cursor, self.program);
-        if self.program.len() - 1 > self.cursor as usize {
-            let n = self.program[self.cursor as usize];
-            self.cursor = self.cursor + 1;
-            Some(n)
-        } else {
-            None
-        }
-    }
+/// Runs the program, reading one integer per line from `reader` and writing every output to
+/// `writer`
+fn run_program<R, W>(code: String, mut reader: R, mut writer: W)
+where
+    R: BufRead,
+    W: Write,
+{
+    let mut program = Intcode::new(code);
+    program.run_with(
+        || {
+            let mut input = String::new();
+            reader
+                .read_line(&mut input)
+                .expect("Unable to read user input");
+            debug!("input: {}", input);
+            Some(
+                input
+                    .trim_end()
+                    .parse()
+                    .expect("cannot parse integer from input"),
+            )
+        },
+        |value| {
+            info!("OUTPUT value: {:?}", value);
+            writeln!(&mut writer, "OUTPUT value: {}", value).expect("Unable to write");
+        },
+    );
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use advent::intcode::{Instruction, InstructionType, ParameterMode};
     #[test]
     fn test_instruction_relative() {
         let inst1 = Instruction::new(1002);
@@ -282,9 +59,8 @@ mod tests {
     where
         R: BufRead,
     {
-        let mut program = Intcode::new(program);
         let mut output = Vec::new();
-        program.run(input, &mut output);
+        run_program(program, input, &mut output);
         String::from_utf8(output).expect("Not UTF-8")
     }
 
@@ -448,22 +224,15 @@ mod tests {
 }
 
 fn run(code: String) {
-    let mut program = Intcode::new(code);
-    debug!("size: {:?}", program.size());
     let stdio = io::stdin();
     let input = stdio.lock();
     let output = io::stdout();
-    program.run(input, output)
+    run_program(code, input, output)
 }
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "day5", about = "Advent of Code - Day 5")]
 struct Opt {
-    /// Activate debug mode
-    // short and long flags (-d, --debug) will be deduced from the field's name
-    #[structopt(short, long)]
-    debug: bool,
-
     /// Input file
     #[structopt(parse(from_os_str))]
     input: PathBuf,
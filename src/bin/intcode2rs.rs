@@ -0,0 +1,42 @@
+//! Translates an Intcode program into a Rust program
+//!
+//! The generated file only depends on the standard library:
+//!
+//!   intcode2rs -o day9.rs input/day9.txt && rustc -O day9.rs && ./day9
+
+use advent::intcode::Intcode;
+use advent::transpiler::{reachable, transpile};
+use log::*;
+use std::fs;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "intcode2rs", about = "Translate an Intcode program into Rust")]
+struct Opt {
+    /// Output file (defaults to stdout)
+    #[structopt(short, long, parse(from_os_str))]
+    output: Option<PathBuf>,
+
+    /// Input file
+    #[structopt(parse(from_os_str))]
+    input: PathBuf,
+}
+
+fn main() {
+    let opt = Opt::from_args();
+    advent::init_logging();
+    let buffer = fs::read_to_string(opt.input).expect("Unable to read input file");
+    let program = Intcode::new(buffer);
+    let memory = program.memory();
+    info!(
+        "{} instructions translated out of {} values",
+        reachable(memory).len(),
+        memory.len()
+    );
+    let source = transpile(memory);
+    match opt.output {
+        Some(path) => fs::write(path, source).expect("Unable to write output file"),
+        None => print!("{}", source),
+    }
+}
@@ -0,0 +1,207 @@
+//! Coverage of Intcode programs
+//!
+//! A `Coverage` counts, for every address, how many times it was executed as an opcode, fetched
+//! as the operand of an instruction, read as data and written as data. Coverages of several runs
+//! of the same program can be merged, then rendered as an annotated disassembly.
+
+use crate::intcode::Instruction;
+use crate::transpiler::reachable;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Coverage {
+    executed: Vec<u64>,
+    operands: Vec<u64>,
+    reads: Vec<u64>,
+    writes: Vec<u64>,
+    runs: usize,
+}
+
+fn bump(counters: &mut Vec<u64>, address: usize) {
+    if address >= counters.len() {
+        counters.resize(address + 1, 0);
+    }
+    counters[address] += 1;
+}
+
+fn count(counters: &[u64], address: usize) -> u64 {
+    counters.get(address).cloned().unwrap_or(0)
+}
+
+fn add(counters: &mut Vec<u64>, other: &[u64]) {
+    if other.len() > counters.len() {
+        counters.resize(other.len(), 0);
+    }
+    for (c, o) in counters.iter_mut().zip(other) {
+        *c += o;
+    }
+}
+
+impl Coverage {
+    /// Returns the coverage of a single run which did nothing yet
+    pub fn new() -> Self {
+        Self {
+            runs: 1,
+            ..Default::default()
+        }
+    }
+
+    pub fn record_instruction(&mut self, address: usize, size: usize) {
+        bump(&mut self.executed, address);
+        for operand in address + 1..address + size {
+            bump(&mut self.operands, operand);
+        }
+    }
+
+    pub fn record_read(&mut self, address: usize) {
+        bump(&mut self.reads, address);
+    }
+
+    pub fn record_write(&mut self, address: usize) {
+        bump(&mut self.writes, address);
+    }
+
+    /// Number of times `address` was executed as an opcode
+    pub fn executed(&self, address: usize) -> u64 {
+        count(&self.executed, address)
+    }
+
+    /// Number of times `address` was fetched as the operand of an instruction
+    pub fn operand(&self, address: usize) -> u64 {
+        count(&self.operands, address)
+    }
+
+    /// Number of times `address` was read as data
+    pub fn reads(&self, address: usize) -> u64 {
+        count(&self.reads, address)
+    }
+
+    /// Number of times `address` was written as data
+    pub fn writes(&self, address: usize) -> u64 {
+        count(&self.writes, address)
+    }
+
+    /// Number of runs merged in this coverage
+    pub fn runs(&self) -> usize {
+        self.runs
+    }
+
+    /// Returns true if `address` was never executed, read nor written
+    pub fn untouched(&self, address: usize) -> bool {
+        self.executed(address) == 0
+            && self.operand(address) == 0
+            && self.reads(address) == 0
+            && self.writes(address) == 0
+    }
+
+    /// Adds the counters of `other` to this coverage
+    pub fn merge(&mut self, other: &Coverage) {
+        add(&mut self.executed, &other.executed);
+        add(&mut self.operands, &other.operands);
+        add(&mut self.reads, &other.reads);
+        add(&mut self.writes, &other.writes);
+        self.runs += other.runs;
+    }
+
+    /// Returns the disassembly of `program` annotated with this coverage
+    ///
+    /// Instructions are the ones which were executed or are statically reachable from address
+    /// 0, the first column giving how many times they ran (`#####` when never). Every other
+    /// address is displayed as data with its reads and writes, `-` marking untouched ones.
+    pub fn render(&self, program: &[i64]) -> String {
+        let statically_reachable = reachable(program);
+        let mut output = String::new();
+        let mut address = 0;
+        writeln!(output, "; coverage of {} run(s)", self.runs).unwrap();
+        while address < program.len() {
+            let instruction = if self.executed(address) > 0 {
+                Instruction::decode(program[address])
+            } else {
+                statically_reachable
+                    .get(&address)
+                    .and_then(|_| Instruction::decode(program[address]))
+            };
+            match instruction {
+                Some(i) if address + i.size() <= program.len() => {
+                    let hits = match self.executed(address) {
+                        0 => "#####".to_string(),
+                        n => n.to_string(),
+                    };
+                    let text = i.disassemble(&program[address + 1..address + i.size()]);
+                    writeln!(output, "{:>8} {:>6}  {}", hits, address, text).unwrap();
+                    address += i.size();
+                },
+                _ => {
+                    let notes = if self.untouched(address) {
+                        "untouched".to_string()
+                    } else {
+                        format!(
+                            "reads: {} writes: {}",
+                            self.reads(address),
+                            self.writes(address)
+                        )
+                    };
+                    let text = format!("data {}", program[address]);
+                    writeln!(
+                        output,
+                        "{:>8} {:>6}  {:<32} ; {}",
+                        "-", address, text, notes
+                    )
+                    .unwrap();
+                    address += 1;
+                },
+            }
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::Intcode;
+
+    fn covered(code: &str, input: i64) -> Coverage {
+        let mut program = Intcode::new(code.to_string());
+        program.enable_coverage();
+        program.run_with(|| Some(input), |_| ());
+        program.coverage().unwrap().clone()
+    }
+
+    #[test]
+    fn test_merge() {
+        let code = "3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9";
+        let mut coverage = covered(code, 0);
+        assert_eq!(coverage.executed(5), 0);
+        assert!(coverage.untouched(14));
+        coverage.merge(&covered(code, 2));
+        assert_eq!(coverage.runs(), 2);
+        assert_eq!(coverage.executed(0), 2);
+        assert_eq!(coverage.executed(5), 1);
+        assert_eq!(coverage.reads(14), 1);
+        assert_eq!(coverage.writes(13), 1);
+        assert!(!coverage.untouched(14));
+    }
+
+    #[test]
+    fn test_render() {
+        let code = "3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9";
+        let program = Intcode::new(code.to_string()).memory().to_vec();
+        let rendered = covered(code, 0).render(&program);
+        assert_eq!(
+            rendered.lines().collect::<Vec<_>>(),
+            vec![
+                "; coverage of 1 run(s)",
+                "       1      0  in [12]",
+                "       1      2  jz [12], [15]",
+                "   #####      5  add [13], [14], [13]",
+                "       1      9  out [13]",
+                "       1     11  hlt",
+                "       -     12  data -1                          ; reads: 1 writes: 1",
+                "       -     13  data 0                           ; reads: 1 writes: 0",
+                "       -     14  data 1                           ; untouched",
+                "       -     15  data 9                           ; reads: 1 writes: 0",
+            ]
+        );
+    }
+}
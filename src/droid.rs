@@ -0,0 +1,272 @@
+//! Repair droid of Advent of Code 2019 Day 15 (https://adventofcode.com/2019/day/15)
+//!
+//! The Intcode program remote controls a droid in an unknown maze: it reads a movement command
+//! (1: north, 2: south, 3: west, 4: east) and replies whether the droid hit a wall (0), moved
+//! (1) or moved onto the oxygen system (2).
+
+use crate::grid::{self, Direction, Point};
+use crate::intcode::Intcode;
+use log::*;
+use petgraph::algo::dijkstra;
+use petgraph::prelude::*;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    Wall,
+    Moved,
+    Oxygen,
+}
+
+impl Status {
+    fn from_code(code: i64) -> Self {
+        match code {
+            0 => Status::Wall,
+            1 => Status::Moved,
+            2 => Status::Oxygen,
+            _ => panic!("Invalid status: {}", code),
+        }
+    }
+}
+
+/// Something able to move the droid one step and tell what happened
+pub trait Remote {
+    fn send(&mut self, direction: Direction) -> Status;
+}
+
+fn command(direction: Direction) -> i64 {
+    match direction {
+        Direction::Up => 1,
+        Direction::Down => 2,
+        Direction::Left => 3,
+        Direction::Right => 4,
+    }
+}
+
+impl Remote for Intcode {
+    fn send(&mut self, direction: Direction) -> Status {
+        let mut input = Some(command(direction));
+        let mut reply = None;
+        self.run_with(|| input.take(), |v| reply = Some(v));
+        Status::from_code(reply.expect("The droid program halted"))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cell {
+    Wall,
+    Open,
+    Oxygen,
+}
+
+/// Part of the maze discovered by the droid, the origin being its starting position
+#[derive(Clone, Debug, Default)]
+pub struct Map {
+    cells: HashMap<Point, Cell>,
+}
+
+impl Map {
+    /// Explores the whole maze reachable from the starting position with a backtracking depth
+    /// first search, leaving the droid back where it started
+    pub fn explore<R: Remote>(remote: &mut R) -> Self {
+        let mut cells = HashMap::new();
+        cells.insert(Point::default(), Cell::Open);
+        let mut position = Point::default();
+        let mut path: Vec<Direction> = Vec::new();
+        loop {
+            let unknown = Direction::all()
+                .iter()
+                .cloned()
+                .find(|d| !cells.contains_key(&(position + d.offset())));
+            match unknown {
+                Some(direction) => {
+                    let next = position + direction.offset();
+                    let status = remote.send(direction);
+                    debug!("droid tries {} and gets {:?}", next, status);
+                    match status {
+                        Status::Wall => {
+                            cells.insert(next, Cell::Wall);
+                        },
+                        Status::Moved | Status::Oxygen => {
+                            let cell = if status == Status::Oxygen {
+                                Cell::Oxygen
+                            } else {
+                                Cell::Open
+                            };
+                            cells.insert(next, cell);
+                            path.push(direction);
+                            position = next;
+                        },
+                    }
+                },
+                None => match path.pop() {
+                    Some(direction) => {
+                        let back = direction.opposite();
+                        assert_ne!(remote.send(back), Status::Wall, "Unable to backtrack");
+                        position = position + back.offset();
+                    },
+                    None => break,
+                },
+            }
+        }
+        Self { cells }
+    }
+
+    pub fn cell(&self, p: &Point) -> Option<Cell> {
+        self.cells.get(p).cloned()
+    }
+
+    /// Position of the oxygen system, if it was found
+    pub fn oxygen(&self) -> Option<Point> {
+        self.cells
+            .iter()
+            .find(|(_, c)| **c == Cell::Oxygen)
+            .map(|(p, _)| *p)
+    }
+
+    /// Returns the graph of the open cells, linked to their open neighbours
+    fn graph(&self) -> UnGraphMap<Point, ()> {
+        let mut g = UnGraphMap::new();
+        for (p, c) in &self.cells {
+            if *c == Cell::Wall {
+                continue;
+            }
+            g.add_node(*p);
+            for n in p.neighbours().iter() {
+                if matches!(self.cell(n), Some(Cell::Open) | Some(Cell::Oxygen)) {
+                    g.add_edge(*p, *n, ());
+                }
+            }
+        }
+        g
+    }
+
+    /// Number of moves from `origin` to every open cell reachable from it
+    pub fn distances(&self, origin: Point) -> HashMap<Point, usize> {
+        let g = self.graph();
+        if !g.contains_node(origin) {
+            return HashMap::new();
+        }
+        dijkstra(&g, origin, None, |_| 1)
+    }
+
+    /// Fewest moves from `origin` to `destination`
+    pub fn shortest_path(&self, origin: Point, destination: Point) -> Option<usize> {
+        let g = self.graph();
+        if !g.contains_node(origin) {
+            return None;
+        }
+        dijkstra(&g, origin, Some(destination), |_| 1)
+            .get(&destination)
+            .cloned()
+    }
+
+    /// Minutes taken by the oxygen to spread from the oxygen system to every open cell
+    pub fn fill_time(&self) -> Option<usize> {
+        let oxygen = self.oxygen()?;
+        self.distances(oxygen).values().max().cloned()
+    }
+
+    /// Renders the map: `#` for walls, `.` for open cells, `O` for the oxygen system and `D`
+    /// for the starting position of the droid
+    pub fn render(&self) -> String {
+        let cells = self
+            .cells
+            .iter()
+            .map(|(p, c)| {
+                let draw = match c {
+                    Cell::Wall => '#',
+                    Cell::Oxygen => 'O',
+                    Cell::Open if p.is_origin() => 'D',
+                    Cell::Open => '.',
+                };
+                (*p, draw)
+            })
+            .collect::<HashMap<_, _>>();
+        grid::render(&cells, ' ', |c| *c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Example of the puzzle, the droid starting on `D`
+    const MAZE: &str = " ##   \n#D.## \n#.#..#\n#.O.# \n ###  \n";
+
+    struct FakeMaze {
+        cells: HashMap<Point, char>,
+        droid: Point,
+        moves: usize,
+    }
+
+    impl FakeMaze {
+        fn new(maze: &str) -> Self {
+            let mut cells = HashMap::new();
+            let mut droid = Point::default();
+            for (y, line) in maze.lines().enumerate() {
+                for (x, c) in line.chars().enumerate() {
+                    let p = Point::new(x as i64, y as i64);
+                    if c == 'D' {
+                        droid = p;
+                    }
+                    cells.insert(p, c);
+                }
+            }
+            Self {
+                cells,
+                droid,
+                moves: 0,
+            }
+        }
+    }
+
+    impl Remote for FakeMaze {
+        fn send(&mut self, direction: Direction) -> Status {
+            let next = self.droid + direction.offset();
+            match self.cells.get(&next).cloned().unwrap_or('#') {
+                '#' | ' ' => Status::Wall,
+                c => {
+                    self.droid = next;
+                    self.moves += 1;
+                    if c == 'O' {
+                        Status::Oxygen
+                    } else {
+                        Status::Moved
+                    }
+                },
+            }
+        }
+    }
+
+    #[test]
+    fn test_explore() {
+        let mut maze = FakeMaze::new(MAZE);
+        let map = Map::explore(&mut maze);
+        assert_eq!(maze.droid, Point::new(1, 1));
+        // every corridor is walked forth and back
+        assert_eq!(maze.moves, 14);
+        assert_eq!(map.oxygen(), Some(Point::new(1, 2)));
+        assert_eq!(map.cell(&Point::new(1, 1)), Some(Cell::Wall));
+        assert_eq!(map.render(), MAZE);
+    }
+
+    #[test]
+    fn test_paths() {
+        let map = Map::explore(&mut FakeMaze::new(MAZE));
+        let oxygen = map.oxygen().unwrap();
+        assert_eq!(map.shortest_path(Point::default(), oxygen), Some(3));
+        assert_eq!(map.shortest_path(Point::default(), Point::new(1, 1)), None);
+        assert_eq!(map.distances(oxygen).len(), 8);
+        assert_eq!(map.fill_time(), Some(4));
+    }
+
+    #[test]
+    fn test_intcode_remote() {
+        // replies "moved" to the first command, and "wall" to the second one
+        let mut program = Intcode::new("3,100,104,1,3,100,104,0,99".to_string());
+        assert_eq!(program.send(Direction::Up), Status::Moved);
+        assert_eq!(program.send(Direction::Left), Status::Wall);
+        assert_eq!(program.read(100), 3);
+    }
+}
@@ -0,0 +1,176 @@
+//! Points and directions on a 2D grid, shared by the puzzles moving something around
+//!
+//! Unlike day 3, the grid follows the screen convention: `y` grows downwards, so that rendering
+//! a grid row by row does not need to flip it.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Add;
+
+/// A point in 2D
+#[derive(Clone, Debug, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Point {
+    pub x: i64,
+    pub y: i64,
+}
+
+impl Point {
+    /// Returns a point based on its coordinates
+    pub fn new(x: i64, y: i64) -> Self {
+        Point { x, y }
+    }
+
+    /// Returns true if the point is on the origin
+    pub fn is_origin(&self) -> bool {
+        self.x == 0 && self.y == 0
+    }
+
+    /// Manhattan distance from the origin
+    pub fn distance(&self) -> i64 {
+        self.x.abs() + self.y.abs()
+    }
+
+    /// Returns the 4 points next to this one, in the order of `Direction::all`
+    pub fn neighbours(&self) -> [Point; 4] {
+        let d = Direction::all();
+        [
+            *self + d[0].offset(),
+            *self + d[1].offset(),
+            *self + d[2].offset(),
+            *self + d[3].offset(),
+        ]
+    }
+}
+
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(x: {}, y: {})", self.x, self.y)
+    }
+}
+
+impl Add for Point {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            x: self.x + other.x,
+            y: self.y + other.y,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Up,
+    Right,
+    Down,
+    Left,
+}
+
+impl Direction {
+    /// Returns every direction, clockwise from `Up`
+    pub fn all() -> [Direction; 4] {
+        [
+            Direction::Up,
+            Direction::Right,
+            Direction::Down,
+            Direction::Left,
+        ]
+    }
+
+    pub fn turn_left(self) -> Self {
+        match self {
+            Direction::Up => Direction::Left,
+            Direction::Left => Direction::Down,
+            Direction::Down => Direction::Right,
+            Direction::Right => Direction::Up,
+        }
+    }
+
+    pub fn turn_right(self) -> Self {
+        match self {
+            Direction::Up => Direction::Right,
+            Direction::Right => Direction::Down,
+            Direction::Down => Direction::Left,
+            Direction::Left => Direction::Up,
+        }
+    }
+
+    pub fn opposite(self) -> Self {
+        self.turn_left().turn_left()
+    }
+
+    /// Returns the move of one step in this direction
+    pub fn offset(self) -> Point {
+        match self {
+            Direction::Up => Point::new(0, -1),
+            Direction::Right => Point::new(1, 0),
+            Direction::Down => Point::new(0, 1),
+            Direction::Left => Point::new(-1, 0),
+        }
+    }
+}
+
+/// Returns the top left and bottom right corners of the smallest box holding every point
+pub fn bounds<'a, I>(points: I) -> Option<(Point, Point)>
+where
+    I: IntoIterator<Item = &'a Point>,
+{
+    points.into_iter().fold(None, |b, p| match b {
+        None => Some((*p, *p)),
+        Some((min, max)) => Some((
+            Point::new(min.x.min(p.x), min.y.min(p.y)),
+            Point::new(max.x.max(p.x), max.y.max(p.y)),
+        )),
+    })
+}
+
+/// Renders the cells of a sparse grid as text, one line per row
+///
+/// Points missing from `cells` are rendered with `empty`.
+pub fn render<T, F>(cells: &HashMap<Point, T>, empty: char, draw: F) -> String
+where
+    F: Fn(&T) -> char,
+{
+    let mut output = String::new();
+    if let Some((min, max)) = bounds(cells.keys()) {
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                output.push(cells.get(&Point::new(x, y)).map_or(empty, &draw));
+            }
+            output.push('\n');
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_turns() {
+        assert_eq!(Direction::Up.turn_left(), Direction::Left);
+        assert_eq!(Direction::Left.turn_right(), Direction::Up);
+        assert_eq!(Direction::Down.opposite(), Direction::Up);
+        for d in Direction::all().iter() {
+            assert_eq!(d.turn_left().turn_right(), *d);
+            assert!((Point::default() + d.offset() + d.opposite().offset()).is_origin());
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let mut cells = HashMap::new();
+        cells.insert(Point::new(-1, 0), true);
+        cells.insert(Point::new(1, 1), false);
+        assert_eq!(
+            bounds(cells.keys()),
+            Some((Point::new(-1, 0), Point::new(1, 1)))
+        );
+        assert_eq!(
+            render(&cells, ' ', |c| if *c { '#' } else { '.' }),
+            "#  \n  .\n"
+        );
+    }
+}
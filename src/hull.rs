@@ -0,0 +1,164 @@
+//! Hull painting robot of Advent of Code 2019 Day 11 (https://adventofcode.com/2019/day/11)
+//!
+//! The robot is driven by an Intcode program: it sends the colour of the panel under the robot,
+//! gets back the colour to paint and the direction to turn (0 for left, 1 for right), then moves
+//! one panel forward.
+
+use crate::grid::{self, Direction, Point};
+use crate::intcode::{Intcode, State};
+use log::*;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Colour {
+    Black,
+    White,
+}
+
+impl Colour {
+    fn from_code(code: i64) -> Self {
+        match code {
+            0 => Colour::Black,
+            1 => Colour::White,
+            _ => panic!("Invalid colour: {}", code),
+        }
+    }
+
+    fn code(self) -> i64 {
+        match self {
+            Colour::Black => 0,
+            Colour::White => 1,
+        }
+    }
+}
+
+impl FromStr for Colour {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "black" => Ok(Colour::Black),
+            "white" => Ok(Colour::White),
+            _ => Err(format!("Invalid colour: {} (expected black or white)", s)),
+        }
+    }
+}
+
+pub struct Robot {
+    position: Point,
+    direction: Direction,
+    start: Colour,
+    panels: HashMap<Point, Colour>,
+}
+
+impl Robot {
+    /// Returns a robot facing up, on a starting panel of colour `start`
+    pub fn new(start: Colour) -> Self {
+        Self {
+            position: Point::default(),
+            direction: Direction::Up,
+            start,
+            panels: HashMap::new(),
+        }
+    }
+
+    /// Returns the colour of the panel at `p`
+    pub fn colour(&self, p: &Point) -> Colour {
+        match self.panels.get(p) {
+            Some(c) => *c,
+            None if p.is_origin() => self.start,
+            None => Colour::Black,
+        }
+    }
+
+    /// Number of panels painted at least once
+    pub fn painted(&self) -> usize {
+        self.panels.len()
+    }
+
+    fn step(&mut self, colour: i64, turn: i64) {
+        self.panels.insert(self.position, Colour::from_code(colour));
+        self.direction = match turn {
+            0 => self.direction.turn_left(),
+            1 => self.direction.turn_right(),
+            _ => panic!("Invalid turn: {}", turn),
+        };
+        self.position = self.position + self.direction.offset();
+        debug!("robot moves {:?} to {}", self.direction, self.position);
+    }
+
+    /// Lets `program` drive the robot until it halts
+    pub fn run(&mut self, program: &mut Intcode) {
+        let mut outputs = Vec::new();
+        loop {
+            let mut input = Some(self.colour(&self.position).code());
+            let state = program.run_with(|| input.take(), |v| outputs.push(v));
+            for order in outputs.chunks_exact(2) {
+                self.step(order[0], order[1]);
+            }
+            let pending = outputs.len() % 2;
+            outputs.drain(..outputs.len() - pending);
+            if state == State::Halted {
+                break;
+            }
+        }
+    }
+
+    /// Renders the hull, `#` being white panels
+    pub fn render(&self) -> String {
+        let mut panels = self.panels.clone();
+        panels.entry(Point::default()).or_insert(self.start);
+        grid::render(&panels, ' ', |c| match c {
+            Colour::White => '#',
+            Colour::Black => ' ',
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a program reading a colour before sending each of the `orders`
+    fn program(orders: &[(i64, i64)]) -> Intcode {
+        let mut code = orders
+            .iter()
+            .map(|(c, t)| format!("3,1000,104,{},104,{}", c, t))
+            .collect::<Vec<_>>();
+        code.push("99".to_string());
+        Intcode::new(code.join(","))
+    }
+
+    #[test]
+    fn test_example() {
+        let mut robot = Robot::new(Colour::Black);
+        let orders = [(1, 0), (0, 0), (1, 0), (1, 0), (0, 1), (1, 0), (1, 0)];
+        robot.run(&mut program(&orders));
+        assert_eq!(robot.painted(), 6);
+        assert_eq!(robot.position, Point::new(0, -1));
+        assert_eq!(robot.direction, Direction::Left);
+        assert_eq!(robot.render(), "  #\n  #\n## \n");
+    }
+
+    #[test]
+    fn test_start_colour() {
+        // paints the colour it reads, then turns right
+        let echo = "3,100,4,100,104,1,99";
+        let mut robot = Robot::new(Colour::White);
+        robot.run(&mut Intcode::new(echo.to_string()));
+        assert_eq!(robot.colour(&Point::default()), Colour::White);
+        assert_eq!(robot.painted(), 1);
+        assert_eq!(robot.render(), "#\n");
+
+        let robot = Robot::new(Colour::White);
+        assert_eq!(robot.painted(), 0);
+        assert_eq!(robot.render(), "#\n");
+    }
+
+    #[test]
+    fn test_colour_from_str() {
+        assert_eq!("white".parse(), Ok(Colour::White));
+        assert!("red".parse::<Colour>().is_err());
+    }
+}
@@ -0,0 +1,573 @@
+//! Intcode computer shared by the puzzles running Intcode programs
+//! (https://adventofcode.com/2019/day/9)
+//!
+//! It supports every instruction up to day 9 (relative mode included) and can be paused when
+//! it waits for an input, then resumed.
+
+use crate::coverage::Coverage;
+use crate::patches::Patches;
+use log::*;
+use std::io::{BufRead, Write};
+
+#[derive(Debug, Clone)]
+pub struct Intcode {
+    program: Vec<i64>,
+    cursor: i64,
+    relative_base: i64,
+    coverage: Option<Coverage>,
+    patches: Option<Patches>,
+    instruction: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InstructionType {
+    Mul,
+    Add,
+    Input,
+    Output,
+    JumpIfTrue,
+    JumpIfFalse,
+    LessThan,
+    Equals,
+    Stop,
+    Adjustbase,
+}
+
+/// Why `run_with` gave the control back
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum State {
+    Halted,
+    WaitingForInput,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParameterMode {
+    Position,
+    Immediate,
+    Relative,
+}
+
+pub struct Instruction {
+    pub instruction_type: InstructionType,
+    pub parameter_mode: Vec<ParameterMode>,
+}
+
+impl Instruction {
+    pub fn new(code: i64) -> Self {
+        Self::decode(code).expect("Unknown instruction")
+    }
+
+    /// Same as `new`, but returns `None` for a value which is not a valid instruction
+    pub fn decode(code: i64) -> Option<Self> {
+        if code < 0 {
+            return None;
+        }
+        let code_as_str = format!("{}", code);
+        let mut code_as_vec: Vec<i64> = code_as_str
+            .into_bytes()
+            .into_iter()
+            .map(|b| b as i64 - 48)
+            .collect::<Vec<i64>>();
+        debug!("code vec: {:?}", code_as_vec);
+        let (inst_type, size) = match code_as_vec.pop() {
+            Some(1) => (InstructionType::Add, 3),
+            Some(2) => (InstructionType::Mul, 3),
+            Some(3) => (InstructionType::Input, 1),
+            Some(4) => (InstructionType::Output, 1),
+            Some(5) => (InstructionType::JumpIfTrue, 2),
+            Some(6) => (InstructionType::JumpIfFalse, 2),
+            Some(7) => (InstructionType::LessThan, 3),
+            Some(8) => (InstructionType::Equals, 3),
+            Some(9) => (InstructionType::Adjustbase, 1),
+            _ => return None,
+        };
+        match code_as_vec.pop() {
+            Some(9) if inst_type == InstructionType::Adjustbase => {
+                return Some(Self {
+                    instruction_type: InstructionType::Stop,
+                    parameter_mode: vec![],
+                });
+            },
+            Some(0) | None => (),
+            Some(_) => return None,
+        }
+        if code_as_vec.len() > size {
+            return None;
+        }
+        let mut parameter_mode = vec![ParameterMode::Position; size];
+        code_as_vec.reverse();
+        for (i, v) in code_as_vec.iter().enumerate() {
+            parameter_mode[i] = match v {
+                0 => ParameterMode::Position,
+                1 => ParameterMode::Immediate,
+                2 => ParameterMode::Relative,
+                _ => return None,
+            };
+        }
+        Some(Self {
+            instruction_type: inst_type,
+            parameter_mode,
+        })
+    }
+
+    /// Number of memory cells used by the instruction, opcode included
+    pub fn size(&self) -> usize {
+        self.parameter_mode.len() + 1
+    }
+
+    /// Returns the mnemonic of the instruction
+    pub fn mnemonic(&self) -> &'static str {
+        match self.instruction_type {
+            InstructionType::Add => "add",
+            InstructionType::Mul => "mul",
+            InstructionType::Input => "in",
+            InstructionType::Output => "out",
+            InstructionType::JumpIfTrue => "jnz",
+            InstructionType::JumpIfFalse => "jz",
+            InstructionType::LessThan => "lt",
+            InstructionType::Equals => "eq",
+            InstructionType::Stop => "hlt",
+            InstructionType::Adjustbase => "arb",
+        }
+    }
+
+    /// Returns the assembly of the instruction, given the values of its parameters
+    ///
+    /// Position parameters are displayed as `[5]`, relative ones as `[rb+5]` and immediate ones
+    /// as their value.
+    pub fn disassemble(&self, parameters: &[i64]) -> String {
+        let operands = self
+            .parameter_mode
+            .iter()
+            .zip(parameters)
+            .map(|(mode, value)| match mode {
+                ParameterMode::Position => format!("[{}]", value),
+                ParameterMode::Immediate => format!("{}", value),
+                ParameterMode::Relative if *value < 0 => format!("[rb{}]", value),
+                ParameterMode::Relative => format!("[rb+{}]", value),
+            })
+            .collect::<Vec<_>>();
+        if operands.is_empty() {
+            self.mnemonic().to_string()
+        } else {
+            format!("{} {}", self.mnemonic(), operands.join(", "))
+        }
+    }
+}
+
+impl Intcode {
+    pub fn new(code: String) -> Self {
+        Self::from_memory(
+            code.trim()
+                .split(',')
+                .map(|s| s.trim().parse().expect("Invalid Intcode value"))
+                .collect(),
+        )
+    }
+
+    pub fn from_memory(program: Vec<i64>) -> Self {
+        Self {
+            program,
+            cursor: 0,
+            relative_base: 0,
+            coverage: None,
+            patches: None,
+            instruction: 0,
+        }
+    }
+
+    /// Returns the whole memory of the computer
+    pub fn memory(&self) -> &[i64] {
+        &self.program
+    }
+
+    /// Returns the value stored at `address`, memory beyond the program being 0
+    pub fn read(&self, address: usize) -> i64 {
+        self.program.get(address).cloned().unwrap_or(0)
+    }
+
+    /// Stores `value` at `address`, growing the memory if needed
+    pub fn write(&mut self, address: usize, value: i64) {
+        if address >= self.program.len() {
+            self.program.resize(address + 1, 0);
+        }
+        self.program[address] = value
+    }
+
+    /// Starts recording which addresses are executed, read and written
+    pub fn enable_coverage(&mut self) {
+        self.coverage = Some(Coverage::new());
+    }
+
+    /// Returns what was recorded since `enable_coverage` was called
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    /// Starts flagging the writes of the program to its own instructions
+    pub fn enable_patch_detection(&mut self) {
+        self.patches = Some(Patches::new(&self.program));
+    }
+
+    /// Returns the self-modifying writes flagged since `enable_patch_detection` was called
+    pub fn patches(&self) -> Option<&Patches> {
+        self.patches.as_ref()
+    }
+
+    fn address(&self, value: i64, pam: &ParameterMode) -> Result<usize, String> {
+        let address = match pam {
+            ParameterMode::Relative => value + self.relative_base,
+            _ => value,
+        };
+        if address < 0 {
+            return Err(format!(
+                "Negative address {} used by the instruction at {}",
+                address, self.instruction
+            ));
+        }
+        Ok(address as usize)
+    }
+
+    fn get(&mut self, pam: &ParameterMode) -> Result<i64, String> {
+        trace!("getting {:?}", pam);
+        debug!("getting: {}", self.cursor);
+        let value = self.next().expect("Unable to get value");
+        debug!("got: {}", value);
+        match pam {
+            ParameterMode::Immediate => Ok(value),
+            _ => {
+                let address = self.address(value, pam)?;
+                if let Some(coverage) = self.coverage.as_mut() {
+                    coverage.record_read(address);
+                }
+                Ok(self.read(address))
+            },
+        }
+    }
+
+    fn set(&mut self, value: i64, pam: &ParameterMode) -> Result<(), String> {
+        let pos = self.next().expect("Unable to get position to set");
+        let pos = self.address(pos, pam)?;
+        trace!("storing {} in {}", value, pos);
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.record_write(pos);
+        }
+        if let Some(patches) = self.patches.as_mut() {
+            patches.record_write(self.instruction, pos);
+        }
+        self.write(pos, value);
+        Ok(())
+    }
+
+    /// Runs the program, reading one integer per line from `reader` and writing every output
+    /// on its own line to `writer`
+    pub fn run<R, W>(&mut self, mut reader: R, mut writer: W)
+    where
+        R: BufRead,
+        W: Write,
+    {
+        self.run_with(
+            || {
+                let mut input = String::new();
+                reader
+                    .read_line(&mut input)
+                    .expect("Unable to read user input");
+                debug!("input: {}", input);
+                Some(
+                    input
+                        .trim_end()
+                        .parse()
+                        .expect("cannot parse integer from input"),
+                )
+            },
+            |value| writeln!(&mut writer, "{}", value).expect("Unable to write"),
+        );
+    }
+
+    /// Runs the program, pulling inputs from `input` and pushing outputs to `output`
+    ///
+    /// This lets the caller plug the computer on anything (a channel, a queue, ...) and not only
+    /// on a reader/writer pair. When `input` has nothing to give, the computer stops on the input
+    /// instruction and a later call resumes from there.
+    ///
+    /// Panics when the program uses a negative address or an unknown opcode, see `try_run_with`.
+    pub fn run_with<I, O>(&mut self, input: I, output: O) -> State
+    where
+        I: FnMut() -> Option<i64>,
+        O: FnMut(i64),
+    {
+        self.try_run_with(input, output)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Same as `run_with`, but returns an error when the program reads or writes a negative
+    /// address or reaches an unknown opcode, after which the computer should not be resumed
+    pub fn try_run_with<I, O>(&mut self, mut input: I, mut output: O) -> Result<State, String>
+    where
+        I: FnMut() -> Option<i64>,
+        O: FnMut(i64),
+    {
+        while let Some(next_instruction) = self.next() {
+            debug!(
+                "opcode {:?} - index: {}",
+                next_instruction % 1000,
+                self.cursor - 1
+            );
+            let address = self.cursor as usize - 1;
+            let op = Instruction::decode(next_instruction)
+                .ok_or_else(|| format!("unknown opcode {} at {}", next_instruction, address))?;
+            self.instruction = address;
+            if op.instruction_type != InstructionType::Input {
+                self.record_instruction(address, &op);
+            }
+            let mut pm = op.parameter_mode.iter();
+            match op.instruction_type {
+                InstructionType::Stop => {
+                    // stay on the stop instruction so that resuming halts again
+                    self.cursor -= 1;
+                    return Ok(State::Halted);
+                },
+                InstructionType::Add => {
+                    let result = self
+                        .get(pm.next().expect("Missing operand"))?
+                        .wrapping_add(self.get(pm.next().expect("missing operand"))?);
+                    debug!("result for add: {}", result);
+                    self.set(result, pm.next().expect("missing operand"))?;
+                },
+                InstructionType::Mul => {
+                    let result = self
+                        .get(pm.next().expect("Missing operand"))?
+                        .wrapping_mul(self.get(pm.next().expect("missing operand"))?);
+                    self.set(result, pm.next().expect("missing operand"))?;
+                },
+                InstructionType::Output => {
+                    let value = self.get(pm.next().expect("Missing operand"))?;
+                    debug!("OUTPUT value: {:?}", value);
+                    output(value);
+                },
+                InstructionType::Input => match input() {
+                    Some(result) => {
+                        self.record_instruction(address, &op);
+                        self.set(result, pm.next().expect("missing operand"))?
+                    },
+                    None => {
+                        self.cursor -= 1;
+                        return Ok(State::WaitingForInput);
+                    },
+                },
+                InstructionType::JumpIfTrue => {
+                    let param1 = self.get(pm.next().expect("Missing operand"))?;
+                    let param2 = self.get(pm.next().expect("Missing operand"))?;
+                    if param1 != 0 {
+                        debug!("changing cursor to {}", param2);
+                        self.cursor = param2;
+                    }
+                },
+                InstructionType::JumpIfFalse => {
+                    let param1 = self.get(pm.next().expect("Missing operand"))?;
+                    let param2 = self.get(pm.next().expect("Missing operand"))?;
+                    if param1 == 0 {
+                        debug!("changing cursor to {}", param2);
+                        self.cursor = param2;
+                    }
+                },
+                InstructionType::LessThan => {
+                    let param1 = self.get(pm.next().expect("Missing operand"))?;
+                    let param2 = self.get(pm.next().expect("Missing operand"))?;
+                    self.set(
+                        (param1 < param2) as i64,
+                        pm.next().expect("missing operand"),
+                    )?;
+                },
+                InstructionType::Equals => {
+                    let param1 = self.get(pm.next().expect("Missing operand"))?;
+                    let param2 = self.get(pm.next().expect("Missing operand"))?;
+                    self.set(
+                        (param1 == param2) as i64,
+                        pm.next().expect("missing operand"),
+                    )?;
+                },
+                InstructionType::Adjustbase => {
+                    let value = self.get(pm.next().expect("Missing operand"))?;
+                    debug!("Ajust base value: {:?}", value);
+                    self.relative_base += value;
+                },
+            };
+        }
+        Ok(State::Halted)
+    }
+
+    fn record_instruction(&mut self, address: usize, op: &Instruction) {
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.record_instruction(address, op.size());
+        }
+        if let Some(patches) = self.patches.as_mut() {
+            patches.record_instruction(address, op.size());
+        }
+    }
+}
+
+impl Iterator for Intcode {
+    type Item = i64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        debug!("getting next: {} on {:?}", self.cursor, self.program);
+        if self.cursor >= 0 && (self.cursor as usize) < self.program.len() {
+            let n = self.program[self.cursor as usize];
+            self.cursor += 1;
+            Some(n)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_instruction() {
+        let inst1 = Instruction::new(1002);
+        assert_eq!(inst1.instruction_type, InstructionType::Mul);
+        assert_eq!(
+            inst1.parameter_mode,
+            vec![
+                ParameterMode::Position,
+                ParameterMode::Immediate,
+                ParameterMode::Position
+            ]
+        );
+    }
+
+    #[test]
+    fn test_instruction_relative() {
+        let inst = Instruction::new(209);
+        assert_eq!(inst.instruction_type, InstructionType::Adjustbase);
+        assert_eq!(inst.parameter_mode, vec![ParameterMode::Relative]);
+        assert_eq!(inst.size(), 2);
+        assert_eq!(Instruction::new(99).instruction_type, InstructionType::Stop);
+        assert_eq!(Instruction::new(99).size(), 1);
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        assert!(Instruction::decode(0).is_none());
+        assert!(Instruction::decode(-1).is_none());
+        assert!(Instruction::decode(19).is_none());
+        assert!(Instruction::decode(301).is_none());
+        assert!(Instruction::decode(11104).is_none());
+    }
+
+    #[test]
+    fn test_day9() {
+        let code = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+        let mut program = Intcode::new(code.to_string());
+        let mut outputs = Vec::new();
+        program.run_with(|| None, |v| outputs.push(v));
+        assert_eq!(outputs, Intcode::new(code.to_string()).memory().to_vec());
+
+        let mut program = Intcode::new("1102,34915192,34915192,7,4,7,99,0".to_string());
+        let mut output = Vec::new();
+        program.run(&b""[..], &mut output);
+        assert_eq!(output, b"1219070632396864\n");
+    }
+
+    #[test]
+    fn test_negative_address() {
+        let mut program = Intcode::new("109,-5,204,0,99".to_string());
+        assert_eq!(
+            program.try_run_with(|| None, |_| ()),
+            Err("Negative address -5 used by the instruction at 2".to_string())
+        );
+        let mut program = Intcode::new("1101,1,1,-1,99".to_string());
+        assert_eq!(
+            program.try_run_with(|| None, |_| ()),
+            Err("Negative address -1 used by the instruction at 0".to_string())
+        );
+        let mut program = Intcode::new("109,-5,21101,1,1,5,4,0,99".to_string());
+        let mut outputs = Vec::new();
+        assert_eq!(
+            program.try_run_with(|| None, |v| outputs.push(v)),
+            Ok(State::Halted)
+        );
+        assert_eq!(outputs, vec![2]);
+    }
+
+    #[test]
+    fn test_unknown_opcode() {
+        let mut program = Intcode::new("1101,1,1,5,42,0,99".to_string());
+        assert_eq!(
+            program.try_run_with(|| None, |_| ()),
+            Err("unknown opcode 42 at 4".to_string())
+        );
+        let mut program = Intcode::new("104,7,-3".to_string());
+        let mut outputs = Vec::new();
+        assert_eq!(
+            program.try_run_with(|| None, |v| outputs.push(v)),
+            Err("unknown opcode -3 at 2".to_string())
+        );
+        assert_eq!(outputs, vec![7]);
+    }
+
+    #[test]
+    fn test_disassemble() {
+        assert_eq!(
+            Instruction::new(1002).disassemble(&[4, 3, 4]),
+            "mul [4], 3, [4]"
+        );
+        assert_eq!(Instruction::new(204).disassemble(&[-1]), "out [rb-1]");
+        assert_eq!(
+            Instruction::new(21101).disassemble(&[1, 2, 3]),
+            "add 1, 2, [rb+3]"
+        );
+        assert_eq!(Instruction::new(99).disassemble(&[]), "hlt");
+    }
+
+    #[test]
+    fn test_coverage() {
+        let mut program = Intcode::new("3,9,8,9,10,9,4,9,99,-1,8".to_string());
+        program.enable_coverage();
+        assert_eq!(program.run_with(|| None, |_| ()), State::WaitingForInput);
+        assert_eq!(program.coverage().unwrap().executed(0), 0);
+        program.run_with(|| Some(8), |_| ());
+        let coverage = program.coverage().unwrap();
+        assert_eq!(coverage.executed(0), 1);
+        assert_eq!(coverage.executed(2), 1);
+        assert_eq!(coverage.executed(8), 1);
+        assert_eq!(coverage.operand(3), 1);
+        assert_eq!(coverage.reads(9), 2);
+        assert_eq!(coverage.reads(10), 1);
+        assert_eq!(coverage.writes(9), 2);
+    }
+
+    #[test]
+    fn test_run_with() {
+        let mut program = Intcode::new("3,9,8,9,10,9,4,9,99,-1,8".to_string());
+        let mut outputs = Vec::new();
+        let state = program.run_with(|| Some(8), |v| outputs.push(v));
+        assert_eq!(state, State::Halted);
+        assert_eq!(outputs, vec![1]);
+    }
+
+    #[test]
+    fn test_resume_on_input() {
+        let mut program = Intcode::new("3,9,8,9,10,9,4,9,99,-1,8".to_string());
+        let mut outputs = Vec::new();
+        assert_eq!(
+            program.run_with(|| None, |v| outputs.push(v)),
+            State::WaitingForInput
+        );
+        assert!(outputs.is_empty());
+        assert_eq!(
+            program.run_with(|| Some(2), |v| outputs.push(v)),
+            State::Halted
+        );
+        assert_eq!(outputs, vec![0]);
+        assert_eq!(
+            program.run_with(|| None, |v| outputs.push(v)),
+            State::Halted
+        );
+        assert_eq!(outputs, vec![0]);
+    }
+}
@@ -1,5 +1,22 @@
 extern crate env_logger;
 
+pub mod adventure;
+pub mod amplifier;
+pub mod arcade;
+pub mod ascii;
+pub mod beam;
+pub mod coverage;
+pub mod droid;
+pub mod grid;
+pub mod hull;
+pub mod intcode;
+pub mod orbit;
+pub mod patches;
+pub mod scaffold;
+pub mod springscript;
+pub mod transpiler;
+pub mod util;
+
 pub fn init_logging() {
     env_logger::builder().format_timestamp_millis().init();
 }
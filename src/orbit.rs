@@ -0,0 +1,600 @@
+//! Orbit maps for Advent of Code 2019 Day 6 (https://adventofcode.com/2019/day/6)
+//!
+//! `parse_orbits` checks that a map is a tree rooted on `COM`. Two ways to measure it are
+//! available:
+//!   - `OrbitMap` runs Dijkstra over a graph where every orbit goes both ways
+//!   - `OrbitTree` computes every depth in one traversal and answers the lowest common ancestor
+//!     of two bodies in logarithmic time, by binary lifting
+
+use crate::util::Random;
+use log::*;
+use petgraph::algo::dijkstra;
+use petgraph::dot::{Config, Dot};
+use petgraph::prelude::*;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+
+/// Problem found in an orbit map, on the line `line` starting at 1
+#[derive(Clone, Debug, PartialEq)]
+pub enum Finding {
+    /// The line is not `A)B`
+    Malformed { line: usize, text: String },
+    /// `body` orbits `parent`, but already orbits another body on `first`
+    SeveralParents {
+        line: usize,
+        body: String,
+        parent: String,
+        first: usize,
+    },
+    /// Bodies orbiting each other, the line being the first orbit of the cycle
+    Cycle { line: usize, bodies: Vec<String> },
+    /// `COM` orbits `parent` instead of being the root
+    ComOrbits { line: usize, parent: String },
+    /// `root` and `others` bodies orbiting it do not orbit `COM`, the line being where `root`
+    /// first appears
+    Disconnected {
+        line: usize,
+        root: String,
+        others: usize,
+    },
+    /// No orbit mentions `COM`, the line being after the last one
+    MissingCom { line: usize },
+}
+
+impl Finding {
+    pub fn line(&self) -> usize {
+        match self {
+            Finding::Malformed { line, .. }
+            | Finding::SeveralParents { line, .. }
+            | Finding::Cycle { line, .. }
+            | Finding::ComOrbits { line, .. }
+            | Finding::Disconnected { line, .. }
+            | Finding::MissingCom { line } => *line,
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: ", self.line())?;
+        match self {
+            Finding::Malformed { text, .. } => {
+                write!(f, "malformed orbit {:?} (expected A)B)", text)
+            },
+            Finding::SeveralParents {
+                body,
+                parent,
+                first,
+                ..
+            } => write!(
+                f,
+                "{} orbits {}, but already orbits another body on line {}",
+                body, parent, first
+            ),
+            Finding::Cycle { bodies, .. } => {
+                write!(f, "cycle {} -> {}", bodies.join(" -> "), bodies[0])
+            },
+            Finding::ComOrbits { parent, .. } => write!(f, "COM orbits {}", parent),
+            Finding::Disconnected { root, others, .. } => match others {
+                0 => write!(f, "{} does not orbit COM", root),
+                1 => write!(f, "{} and the body orbiting it do not orbit COM", root),
+                _ => write!(
+                    f,
+                    "{} and the {} bodies orbiting it do not orbit COM",
+                    root, others
+                ),
+            },
+            Finding::MissingCom { .. } => write!(f, "no orbit around COM"),
+        }
+    }
+}
+
+/// Returns the orbits of a map as (parent, body) pairs, or everything preventing the map from
+/// being a tree rooted on `COM`
+pub fn parse_orbits(text: &str) -> Result<Vec<(String, String)>, Vec<Finding>> {
+    let mut findings = Vec::new();
+    let mut orbits = Vec::new();
+    // parent of every body with the line of the orbit, and where the bodies first appear
+    let mut parents: HashMap<&str, (&str, usize)> = HashMap::new();
+    let mut bodies: Vec<&str> = Vec::new();
+    let mut first_line: HashMap<&str, usize> = HashMap::new();
+    let valid = |name: &str| !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric());
+    let mut last = 0;
+    for (i, raw) in text.lines().enumerate() {
+        let line = i + 1;
+        last = line;
+        let trimmed = raw.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        let names: Vec<&str> = trimmed.split(')').collect();
+        if names.len() != 2 || !names.iter().all(|n| valid(n)) {
+            findings.push(Finding::Malformed {
+                line,
+                text: raw.to_string(),
+            });
+            continue;
+        }
+        let (parent, body) = (names[0], names[1]);
+        for name in &[parent, body] {
+            if !first_line.contains_key(name) {
+                first_line.insert(name, line);
+                bodies.push(name);
+            }
+        }
+        match parents.get(body) {
+            Some((p, _)) if *p == parent => continue,
+            Some((_, first)) => findings.push(Finding::SeveralParents {
+                line,
+                body: body.to_string(),
+                parent: parent.to_string(),
+                first: *first,
+            }),
+            None => {
+                parents.insert(body, (parent, line));
+                orbits.push((parent.to_string(), body.to_string()));
+            },
+        }
+    }
+
+    // follow the parents of every body up to a root, or around a cycle
+    let mut roots: HashMap<&str, Option<&str>> = HashMap::new();
+    for body in &bodies {
+        let mut path = Vec::new();
+        let mut current = *body;
+        let root = loop {
+            if let Some(root) = roots.get(current) {
+                break *root;
+            }
+            if let Some(start) = path.iter().position(|b| *b == current) {
+                let cycle: Vec<&str> = path[start..].to_vec();
+                findings.push(Finding::Cycle {
+                    line: cycle.iter().map(|b| parents[b].1).min().unwrap(),
+                    bodies: cycle.iter().map(|b| b.to_string()).collect(),
+                });
+                break None;
+            }
+            path.push(current);
+            match parents.get(current) {
+                Some((parent, _)) => current = parent,
+                None => break Some(current),
+            }
+        };
+        for b in path {
+            roots.insert(b, root);
+        }
+    }
+
+    // the bodies sharing the root of COM are reported with COM orbiting another body
+    let mut com_root = "COM";
+    if let Some((parent, line)) = parents.get("COM") {
+        findings.push(Finding::ComOrbits {
+            line: *line,
+            parent: parent.to_string(),
+        });
+        com_root = roots["COM"].unwrap_or(com_root);
+    }
+    let mut components: HashMap<&str, usize> = HashMap::new();
+    for root in roots.values().flatten() {
+        *components.entry(root).or_default() += 1;
+    }
+    for (root, size) in components {
+        if root != com_root {
+            findings.push(Finding::Disconnected {
+                line: first_line[root],
+                root: root.to_string(),
+                others: size - 1,
+            });
+        }
+    }
+    if !first_line.contains_key("COM") {
+        findings.push(Finding::MissingCom { line: last + 1 });
+    }
+    if findings.is_empty() {
+        Ok(orbits)
+    } else {
+        findings.sort_by_key(|f| f.line());
+        Err(findings)
+    }
+}
+
+/// Orbits as a graph, to measure them with Dijkstra
+pub struct OrbitMap<'a> {
+    pub map: Graph<&'a str, ()>,
+}
+
+impl<'a> OrbitMap<'a> {
+    pub fn new(g: DiGraphMap<&'a str, ()>) -> Self {
+        Self {
+            map: g.into_graph(),
+        }
+    }
+
+    /// Returns the graph of (parent, body) pairs, every orbit going both ways
+    pub fn from_orbits(orbits: &'a [(String, String)]) -> Self {
+        let mut g = DiGraphMap::new();
+        for (parent, body) in orbits {
+            g.add_edge(parent.as_str(), body.as_str(), ());
+            g.add_edge(body.as_str(), parent.as_str(), ());
+        }
+        OrbitMap::new(g)
+    }
+
+    /// Returns the distance from `origin` to `destination`, or the sum of the distances from
+    /// `origin` to every body without destination
+    pub fn distance(&self, origin: &str, destination: Option<&str>) -> usize {
+        let origin_node = self
+            .map
+            .node_indices()
+            .find(|i| self.map[*i] == origin)
+            .unwrap();
+
+        let destination_node = match destination {
+            Some(d) => self.map.node_indices().find(|i| self.map[*i] == d),
+            None => None,
+        };
+        debug!(
+            "Trying to find path between {:?} and {:?}",
+            origin_node, destination_node
+        );
+        let values = dijkstra(&self.map, origin_node, destination_node, |_| 1);
+        debug!("{:?}", values);
+        if destination.is_none() {
+            let values = values.values();
+            values.sum::<usize>()
+        } else {
+            values[&destination_node.expect("unable to find destination")]
+        }
+    }
+
+    pub fn draw(&self) -> Dot<'_, &Graph<&'a str, ()>> {
+        Dot::with_config(&self.map, &[Config::EdgeNoLabel])
+    }
+}
+
+/// Distance between two bodies of a map with Dijkstra, see `OrbitMap::distance`
+pub fn distance(code: &str, origin: &str, destination: Option<&str>) -> usize {
+    let codes = parse_orbits(code).expect("Invalid orbit map");
+    OrbitMap::from_orbits(&codes).distance(origin, destination)
+}
+
+pub fn part_one(code: &str) -> usize {
+    distance(code, "COM", None)
+}
+
+pub fn part_two(code: &str) -> usize {
+    distance(code, "SAN", Some("YOU")) - 2
+}
+
+/// Orbits as a tree rooted on `COM`
+///
+/// A breadth-first traversal from `COM` gives every depth in one pass. `up[k][b]` is the
+/// ancestor `2^k` orbits above `b`, `COM` being its own parent, so that any ancestor is reached
+/// in a logarithmic number of jumps.
+#[derive(Clone, Debug)]
+pub struct OrbitTree {
+    names: Vec<String>,
+    index: HashMap<String, usize>,
+    depth: Vec<usize>,
+    up: Vec<Vec<usize>>,
+}
+
+impl OrbitTree {
+    /// Returns the tree of (parent, body) pairs, which must form a tree rooted on `COM` as
+    /// checked by `parse_orbits`
+    pub fn from_orbits(orbits: &[(String, String)]) -> Self {
+        let com = 0;
+        let mut names = vec!["COM".to_string()];
+        let mut index: HashMap<String, usize> = HashMap::new();
+        index.insert("COM".to_string(), com);
+        for name in orbits.iter().flat_map(|(p, b)| vec![p, b]) {
+            if !index.contains_key(name) {
+                index.insert(name.clone(), names.len());
+                names.push(name.clone());
+            }
+        }
+        let mut children: Vec<Vec<usize>> = vec![Vec::new(); names.len()];
+        for (parent, body) in orbits {
+            children[index[parent]].push(index[body]);
+        }
+
+        let count = names.len();
+        let mut depth = vec![0; count];
+        let mut parent = vec![com; count];
+        let mut queue = VecDeque::from(vec![com]);
+        while let Some(b) = queue.pop_front() {
+            for &c in &children[b] {
+                depth[c] = depth[b] + 1;
+                parent[c] = b;
+                queue.push_back(c);
+            }
+        }
+        let deepest = depth.iter().cloned().max().unwrap_or(0);
+        let mut up = vec![parent];
+        while 1 << up.len() <= deepest {
+            let last = up.last().unwrap();
+            let next = last.iter().map(|&a| last[a]).collect();
+            up.push(next);
+        }
+        OrbitTree {
+            names,
+            index,
+            depth,
+            up,
+        }
+    }
+
+    /// Number of bodies, `COM` included
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Always false, `COM` being part of every tree
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Number of direct and indirect orbits of a body
+    pub fn depth(&self, body: &str) -> Option<usize> {
+        self.index.get(body).map(|&b| self.depth[b])
+    }
+
+    /// Total number of direct and indirect orbits, the checksum of the map
+    pub fn checksum(&self) -> usize {
+        self.depth.iter().sum()
+    }
+
+    /// Returns the body `steps` orbits above `b`, `COM` when climbing past it
+    fn ancestor(&self, mut b: usize, steps: usize) -> usize {
+        for (k, up) in self.up.iter().enumerate() {
+            if steps & (1 << k) != 0 {
+                b = up[b];
+            }
+        }
+        b
+    }
+
+    fn lca_index(&self, a: usize, b: usize) -> usize {
+        let (a, b) = if self.depth[a] < self.depth[b] {
+            (b, a)
+        } else {
+            (a, b)
+        };
+        let mut a = self.ancestor(a, self.depth[a] - self.depth[b]);
+        let mut b = b;
+        if a == b {
+            return a;
+        }
+        for up in self.up.iter().rev() {
+            if up[a] != up[b] {
+                a = up[a];
+                b = up[b];
+            }
+        }
+        self.up[0][a]
+    }
+
+    /// Returns the lowest body orbited directly or indirectly by both bodies, or one of them if
+    /// the other orbits it
+    pub fn lca(&self, a: &str, b: &str) -> Option<&str> {
+        let (a, b) = (*self.index.get(a)?, *self.index.get(b)?);
+        Some(&self.names[self.lca_index(a, b)])
+    }
+
+    /// Number of orbits between two bodies
+    pub fn distance(&self, a: &str, b: &str) -> Option<usize> {
+        let (a, b) = (*self.index.get(a)?, *self.index.get(b)?);
+        let common = self.lca_index(a, b);
+        Some(self.depth[a] + self.depth[b] - 2 * self.depth[common])
+    }
+
+    /// Number of orbital transfers to move from the body `a` orbits to the one `b` orbits
+    pub fn transfers(&self, a: &str, b: &str) -> Option<usize> {
+        self.distance(a, b).map(|d| d.saturating_sub(2))
+    }
+}
+
+impl FromStr for OrbitTree {
+    type Err = Vec<Finding>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_orbits(s).map(|orbits| OrbitTree::from_orbits(&orbits))
+    }
+}
+
+/// Returns a map of `bodies` bodies besides `COM`, the same seed giving the same map
+///
+/// Every body orbits one of the 16 bodies named before it, which makes deep trees.
+pub fn generate(bodies: usize, seed: u64) -> String {
+    let mut random = Random::new(seed);
+    let name = |i: usize| {
+        if i == 0 {
+            "COM".to_string()
+        } else {
+            format!("B{}", i)
+        }
+    };
+    let mut map = String::new();
+    for i in 1..=bodies {
+        let parent = i - 1 - (random.next_u64() % i.min(16) as u64) as usize;
+        map += &format!("{}){}\n", name(parent), name(i));
+    }
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_basic_map() {
+        let map_string = "COM)B
+B)C
+C)D
+D)E
+E)F
+B)G
+G)H
+D)I
+E)J
+J)K
+K)L"
+        .to_string();
+        assert_eq!(distance(&map_string, "COM", None), 42);
+        assert_eq!(distance(&map_string, "B", Some("E")), 3);
+    }
+
+    #[test]
+    fn test_parse_orbits() {
+        let map_string = "COM)B
+B)C
+C D
+B)D
+E)D
+P)Q
+Q)R
+R)P
+X)Y
+Y)Z
+)A";
+        let findings = parse_orbits(map_string).unwrap_err();
+        let lines = findings.iter().map(Finding::line).collect::<Vec<_>>();
+        assert_eq!(lines, vec![3, 5, 5, 6, 9, 11]);
+        assert_eq!(
+            findings[1],
+            Finding::SeveralParents {
+                line: 5,
+                body: "D".to_string(),
+                parent: "E".to_string(),
+                first: 4
+            }
+        );
+        assert_eq!(
+            findings[2],
+            Finding::Disconnected {
+                line: 5,
+                root: "E".to_string(),
+                others: 0
+            }
+        );
+        let messages = findings.iter().map(|f| f.to_string()).collect::<Vec<_>>();
+        assert_eq!(messages[2], "line 5: E does not orbit COM");
+        assert_eq!(
+            messages[0],
+            "line 3: malformed orbit \"C D\" (expected A)B)"
+        );
+        assert_eq!(messages[3], "line 6: cycle P -> R -> Q -> P");
+        assert_eq!(
+            messages[4],
+            "line 9: X and the 2 bodies orbiting it do not orbit COM"
+        );
+        assert_eq!(
+            messages[5],
+            "line 11: malformed orbit \")A\" (expected A)B)"
+        );
+
+        let findings = parse_orbits("A)B\nB)C\nC)C\n").unwrap_err();
+        assert_eq!(
+            findings.iter().map(|f| f.to_string()).collect::<Vec<_>>(),
+            vec![
+                "line 1: A and the 2 bodies orbiting it do not orbit COM",
+                "line 3: C orbits C, but already orbits another body on line 2",
+                "line 4: no orbit around COM",
+            ]
+        );
+        assert_eq!(parse_orbits("COM)A\n\nA)B\nCOM)A\n").unwrap().len(), 2);
+        assert_eq!(
+            parse_orbits("X)COM\nCOM)A\nY)Z\n").unwrap_err(),
+            vec![
+                Finding::ComOrbits {
+                    line: 1,
+                    parent: "X".to_string()
+                },
+                Finding::Disconnected {
+                    line: 3,
+                    root: "Y".to_string(),
+                    others: 1
+                },
+            ]
+        );
+        assert_eq!(
+            parse_orbits("X)COM\nCOM)A\n").unwrap_err()[0].to_string(),
+            "line 1: COM orbits X"
+        );
+        assert_eq!(
+            parse_orbits("COM)A\nB)B\n").unwrap_err()[0].to_string(),
+            "line 2: cycle B -> B"
+        );
+    }
+
+    #[test]
+    fn test_tree() {
+        let map_string = "COM)B
+B)C
+C)D
+D)E
+E)F
+B)G
+G)H
+D)I
+E)J
+J)K
+K)L
+K)YOU
+I)SAN";
+        let tree: OrbitTree = map_string.parse().unwrap();
+        assert_eq!(tree.len(), 14);
+        assert_eq!(tree.depth("COM"), Some(0));
+        assert_eq!(tree.depth("L"), Some(7));
+        assert_eq!(tree.depth("X"), None);
+        assert_eq!(tree.checksum(), 42 + 7 + 5);
+        assert_eq!(tree.lca("YOU", "SAN"), Some("D"));
+        assert_eq!(tree.lca("H", "L"), Some("B"));
+        assert_eq!(tree.lca("E", "L"), Some("E"));
+        assert_eq!(tree.lca("COM", "COM"), Some("COM"));
+        assert_eq!(tree.distance("B", "E"), Some(3));
+        assert_eq!(tree.transfers("YOU", "SAN"), Some(4));
+        assert_eq!(tree.transfers("YOU", "X"), None);
+        assert!("COM)A\nB)C".parse::<OrbitTree>().is_err());
+    }
+
+    #[test]
+    fn test_tree_matches_dijkstra() {
+        for seed in 0..5 {
+            let map_string = generate(300, seed);
+            assert_eq!(map_string, generate(300, seed));
+            let tree: OrbitTree = map_string.parse().unwrap();
+            assert_eq!(tree.checksum(), part_one(&map_string));
+            let orbits = parse_orbits(&map_string).unwrap();
+            let graph = OrbitMap::from_orbits(&orbits);
+            for (a, b) in &[
+                ("B1", "B300"),
+                ("B299", "B17"),
+                ("B42", "B42"),
+                ("COM", "B150"),
+            ] {
+                assert_eq!(tree.distance(a, b), Some(graph.distance(a, Some(b))));
+            }
+        }
+    }
+
+    #[test]
+    fn test_shortest_path() {
+        let map_string = "COM)B
+B)C
+C)D
+D)E
+E)F
+B)G
+G)H
+D)I
+E)J
+J)K
+K)L
+K)YOU
+I)SAN"
+            .to_string();
+        assert_eq!(distance(&map_string, "YOU", Some("SAN")), 6);
+    }
+}
@@ -0,0 +1,217 @@
+//! Detection of self-modifying Intcode programs
+//!
+//! `Patches` watches a run and flags every write to an address which was executed as an opcode
+//! or an operand before the write, or which gets executed after it. Such writes make the
+//! disassembly of the original program misleading.
+
+use crate::intcode::Instruction;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+/// Part of an instruction held by a patched address
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Role {
+    Opcode,
+    Operand,
+}
+
+/// Whether the patched address was executed before or after the write
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum When {
+    AfterExecution,
+    BeforeExecution,
+}
+
+/// A write of the instruction at `writer` to the code at `address`, seen `count` times
+#[derive(Debug, Clone, PartialEq)]
+pub struct Patch {
+    pub writer: usize,
+    pub address: usize,
+    pub role: Role,
+    pub when: When,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Patches {
+    original: Vec<i64>,
+    executed: HashMap<usize, Role>,
+    pending: HashMap<usize, HashSet<usize>>,
+    found: Vec<Patch>,
+    index: HashMap<(usize, usize, When), usize>,
+}
+
+impl Patches {
+    /// Starts watching a program whose memory is currently `program`
+    pub fn new(program: &[i64]) -> Self {
+        Self {
+            original: program.to_vec(),
+            executed: HashMap::new(),
+            pending: HashMap::new(),
+            found: Vec::new(),
+            index: HashMap::new(),
+        }
+    }
+
+    fn flag(&mut self, writer: usize, address: usize, role: Role, when: When) {
+        match self.index.get(&(writer, address, when)) {
+            Some(i) => self.found[*i].count += 1,
+            None => {
+                self.index.insert((writer, address, when), self.found.len());
+                self.found.push(Patch {
+                    writer,
+                    address,
+                    role,
+                    when,
+                    count: 1,
+                });
+            },
+        }
+    }
+
+    /// Records that the instruction at `address`, `size` cells long, is about to run
+    pub fn record_instruction(&mut self, address: usize, size: usize) {
+        for cell in address..address + size {
+            let role = if cell == address {
+                Role::Opcode
+            } else {
+                Role::Operand
+            };
+            if let Some(writers) = self.pending.remove(&cell) {
+                let mut writers = writers.into_iter().collect::<Vec<_>>();
+                writers.sort_unstable();
+                for writer in writers {
+                    self.flag(writer, cell, role, When::BeforeExecution);
+                }
+            }
+            let known = self.executed.entry(cell).or_insert(role);
+            if role == Role::Opcode {
+                *known = Role::Opcode;
+            }
+        }
+    }
+
+    /// Records that the instruction at `writer` stores a value at `address`
+    pub fn record_write(&mut self, writer: usize, address: usize) {
+        match self.executed.get(&address) {
+            Some(role) => self.flag(writer, address, *role, When::AfterExecution),
+            None => {
+                self.pending.entry(address).or_default().insert(writer);
+            },
+        }
+    }
+
+    /// Returns the patches found so far, in the order they were first seen
+    pub fn found(&self) -> &[Patch] {
+        &self.found
+    }
+
+    /// Returns a report of the patches, writers being disassembled from the memory the program
+    /// had before being watched
+    pub fn summary(&self) -> String {
+        let program = &self.original;
+        let mut output = String::new();
+        writeln!(output, "{} self-modifying write(s) found", self.found.len()).unwrap();
+        for patch in &self.found {
+            let writer = program
+                .get(patch.writer)
+                .and_then(|c| Instruction::decode(*c))
+                .filter(|i| patch.writer + i.size() <= program.len())
+                .map(|i| i.disassemble(&program[patch.writer + 1..patch.writer + i.size()]))
+                .unwrap_or_else(|| "?".to_string());
+            let role = match patch.role {
+                Role::Opcode => "opcode",
+                Role::Operand => "operand",
+            };
+            let when = match patch.when {
+                When::AfterExecution => "already executed",
+                When::BeforeExecution => "executed later",
+            };
+            writeln!(
+                output,
+                "  {:>6}  {:<28} patches {} at {} ({}), {} time(s)",
+                patch.writer, writer, role, patch.address, when, patch.count
+            )
+            .unwrap();
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::Intcode;
+
+    fn patches(code: &str) -> Vec<Patch> {
+        let mut program = Intcode::new(code.to_string());
+        program.enable_patch_detection();
+        program.run_with(|| None, |_| ());
+        program.patches().unwrap().found().to_vec()
+    }
+
+    #[test]
+    fn test_no_patch() {
+        assert_eq!(patches("1,9,10,11,4,11,99,0,0,3,4,0"), vec![]);
+    }
+
+    #[test]
+    fn test_patch_executed_later() {
+        // the first instruction writes the stop instruction
+        assert_eq!(
+            patches("1101,100,-1,4,0"),
+            vec![Patch {
+                writer: 0,
+                address: 4,
+                role: Role::Opcode,
+                when: When::BeforeExecution,
+                count: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_patch_already_executed() {
+        // day 2 example: the result is stored in the operand of the first instruction
+        assert_eq!(
+            patches("1,9,10,3,2,3,11,0,99,30,40,50"),
+            vec![
+                Patch {
+                    writer: 0,
+                    address: 3,
+                    role: Role::Operand,
+                    when: When::AfterExecution,
+                    count: 1,
+                },
+                Patch {
+                    writer: 4,
+                    address: 0,
+                    role: Role::Opcode,
+                    when: When::AfterExecution,
+                    count: 1,
+                }
+            ]
+        );
+        // the loop counter lives in the operand of the jump instruction
+        let found = patches("101,-1,7,7,4,7,1105,11,0,99");
+        assert_eq!(found.len(), 2);
+        assert_eq!(
+            (found[0].writer, found[0].address, found[0].role),
+            (0, 7, Role::Operand)
+        );
+        assert_eq!((found[0].when, found[0].count), (When::BeforeExecution, 1));
+        assert_eq!((found[1].when, found[1].count), (When::AfterExecution, 10));
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut program = Intcode::new("1101,100,-1,4,0".to_string());
+        program.enable_patch_detection();
+        program.run_with(|| None, |_| ());
+        assert_eq!(
+            program.patches().unwrap().summary(),
+            "1 self-modifying write(s) found\n       0  add 100, -1, [4]             patches \
+             opcode at 4 (executed later), 1 time(s)\n"
+        );
+    }
+}
@@ -0,0 +1,341 @@
+//! Scaffold camera and vacuum robot of Advent of Code 2019 Day 17
+//! (https://adventofcode.com/2019/day/17)
+//!
+//! The ASCII program first shows the camera view of the scaffold, `#` being scaffold and
+//! `^`, `v`, `<` or `>` the robot. Woken up, it reads a main movement routine and the three
+//! functions it calls, then reports the collected dust.
+
+use crate::ascii;
+use crate::grid::{Direction, Point};
+use crate::intcode::Intcode;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Maximum number of characters of a routine, commas included
+pub const LIMIT: usize = 20;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Turn {
+    Left,
+    Right,
+}
+
+/// One segment of the path: a turn followed by a number of moves forward
+///
+/// Only the first step can go straight on, when the robot already faces the scaffold.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Step {
+    pub turn: Option<Turn>,
+    pub distance: usize,
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.turn {
+            Some(Turn::Left) => write!(f, "L,{}", self.distance),
+            Some(Turn::Right) => write!(f, "R,{}", self.distance),
+            None => write!(f, "{}", self.distance),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Camera {
+    cells: HashMap<Point, char>,
+}
+
+impl Camera {
+    /// Reads a camera view, one line per row
+    pub fn new(view: &str) -> Self {
+        let mut cells = HashMap::new();
+        for (y, line) in view.lines().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                cells.insert(Point::new(x as i64, y as i64), c);
+            }
+        }
+        Self { cells }
+    }
+
+    /// Runs `program` until it waits for the movement routines, and reads the view it shows
+    pub fn capture(program: &mut Intcode) -> Self {
+        let reply = ascii::send(program, "");
+        Self::new(reply.text.trim_end())
+    }
+
+    fn is_scaffold(&self, p: &Point) -> bool {
+        matches!(
+            self.cells.get(p),
+            Some('#') | Some('^') | Some('v') | Some('<') | Some('>')
+        )
+    }
+
+    /// Returns the scaffold cells having scaffold on their 4 sides
+    pub fn intersections(&self) -> Vec<Point> {
+        let mut points = self
+            .cells
+            .keys()
+            .filter(|p| self.is_scaffold(p) && p.neighbours().iter().all(|n| self.is_scaffold(n)))
+            .cloned()
+            .collect::<Vec<_>>();
+        points.sort_by_key(|p| (p.y, p.x));
+        points
+    }
+
+    /// Sum of the alignment parameters of the intersections
+    pub fn alignment(&self) -> i64 {
+        self.intersections().iter().map(|p| p.x * p.y).sum()
+    }
+
+    /// Position and direction of the robot, unless it fell off the scaffold
+    pub fn robot(&self) -> Option<(Point, Direction)> {
+        self.cells.iter().find_map(|(p, c)| {
+            let direction = match c {
+                '^' => Direction::Up,
+                '>' => Direction::Right,
+                'v' => Direction::Down,
+                '<' => Direction::Left,
+                _ => return None,
+            };
+            Some((*p, direction))
+        })
+    }
+
+    /// Returns the path visiting the whole scaffold, going straight over intersections
+    pub fn path(&self) -> Vec<Step> {
+        let mut steps = Vec::new();
+        let (mut position, mut direction) = match self.robot() {
+            Some(robot) => robot,
+            None => return steps,
+        };
+        let mut turn = None;
+        loop {
+            let mut distance = 0;
+            while self.is_scaffold(&(position + direction.offset())) {
+                position = position + direction.offset();
+                distance += 1;
+            }
+            if distance > 0 {
+                steps.push(Step { turn, distance });
+            }
+            turn = if self.is_scaffold(&(position + direction.turn_left().offset())) {
+                Some(Turn::Left)
+            } else if self.is_scaffold(&(position + direction.turn_right().offset())) {
+                Some(Turn::Right)
+            } else {
+                break;
+            };
+            direction = match turn {
+                Some(Turn::Left) => direction.turn_left(),
+                _ => direction.turn_right(),
+            };
+        }
+        steps
+    }
+}
+
+/// Main movement routine and the functions A, B and C it calls
+#[derive(Clone, Debug, PartialEq)]
+pub struct Routines {
+    pub main: String,
+    pub functions: Vec<String>,
+}
+
+impl Routines {
+    /// Returns the input expected by the robot, `video` asking for a continuous video feed
+    pub fn input(&self, video: bool) -> String {
+        let mut lines = vec![self.main.clone()];
+        lines.extend(self.functions.iter().cloned());
+        while lines.len() < 4 {
+            lines.push(String::new());
+        }
+        lines.push(if video { "y" } else { "n" }.to_string());
+        lines.join("\n") + "\n"
+    }
+}
+
+fn join<T: fmt::Display>(items: &[T]) -> String {
+    items
+        .iter()
+        .map(|i| i.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn search<'a, T: fmt::Display + PartialEq>(
+    path: &'a [T],
+    functions: &mut Vec<&'a [T]>,
+    main: &mut Vec<usize>,
+) -> bool {
+    if path.is_empty() {
+        return true;
+    }
+    // each call takes a letter and a comma in the main routine
+    if 2 * main.len() + 1 > LIMIT {
+        return false;
+    }
+    for i in 0..functions.len() {
+        let function = functions[i];
+        if path.starts_with(function) {
+            main.push(i);
+            if search(&path[function.len()..], functions, main) {
+                return true;
+            }
+            main.pop();
+        }
+    }
+    if functions.len() < 3 {
+        for len in 1..=path.len() {
+            if join(&path[..len]).len() > LIMIT {
+                break;
+            }
+            main.push(functions.len());
+            functions.push(&path[..len]);
+            if search(&path[len..], functions, main) {
+                return true;
+            }
+            functions.pop();
+            main.pop();
+        }
+    }
+    false
+}
+
+/// Splits `path` into at most three functions called by a main routine, each of them fitting
+/// in `LIMIT` characters
+///
+/// Functions are made of whole items of the path, so that a step is never cut between its turn
+/// and its distance.
+pub fn compress<T: fmt::Display + PartialEq>(path: &[T]) -> Option<Routines> {
+    let mut functions = Vec::new();
+    let mut main = Vec::new();
+    if !search(path, &mut functions, &mut main) {
+        return None;
+    }
+    let names = main
+        .iter()
+        .map(|i| (b'A' + *i as u8) as char)
+        .collect::<Vec<_>>();
+    Some(Routines {
+        main: join(&names),
+        functions: functions.iter().map(|f| join(f)).collect(),
+    })
+}
+
+/// Wakes the robot of a freshly loaded `program` up and sends the routines
+///
+/// The value of the reply is the dust collected, and its text holds the prompts and, with
+/// `video`, every frame of the feed.
+pub fn deliver(mut program: Intcode, routines: &Routines, video: bool) -> ascii::Reply {
+    program.write(0, 2);
+    ascii::send(&mut program, &routines.input(video))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CALIBRATION: &str = "..#..........\n\
+                               ..#..........\n\
+                               #######...###\n\
+                               #.#...#...#.#\n\
+                               #############\n\
+                               ..#...#...#..\n\
+                               ..#####...^..";
+
+    const SCAFFOLD: &str = "#######...#####\n\
+                            #.....#...#...#\n\
+                            #.....#...#...#\n\
+                            ......#...#...#\n\
+                            ......#...###.#\n\
+                            ......#.....#.#\n\
+                            ^########...#.#\n\
+                            ......#.#...#.#\n\
+                            ......#########\n\
+                            ........#...#..\n\
+                            ....#########..\n\
+                            ....#...#......\n\
+                            ....#...#......\n\
+                            ....#...#......\n\
+                            ....#####......";
+
+    fn expand(routines: &Routines) -> String {
+        routines
+            .main
+            .split(',')
+            .map(|name| routines.functions[(name.as_bytes()[0] - b'A') as usize].clone())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    #[test]
+    fn test_alignment() {
+        let camera = Camera::new(CALIBRATION);
+        assert_eq!(
+            camera.intersections(),
+            vec![
+                Point::new(2, 2),
+                Point::new(2, 4),
+                Point::new(6, 4),
+                Point::new(10, 4)
+            ]
+        );
+        assert_eq!(camera.alignment(), 76);
+    }
+
+    #[test]
+    fn test_path() {
+        let camera = Camera::new(SCAFFOLD);
+        assert_eq!(camera.robot(), Some((Point::new(0, 6), Direction::Up)));
+        assert_eq!(
+            join(&camera.path()),
+            "R,8,R,8,R,4,R,4,R,8,L,6,L,2,R,4,R,4,R,8,R,8,R,8,L,6,L,2"
+        );
+    }
+
+    #[test]
+    fn test_path_straight_ahead() {
+        let camera = Camera::new("#####\n#...#\n^...#");
+        let path = camera.path();
+        assert_eq!(
+            path[0],
+            Step {
+                turn: None,
+                distance: 2
+            }
+        );
+        assert_eq!(join(&path), "2,R,4,R,2");
+    }
+
+    #[test]
+    fn test_compress() {
+        let path = Camera::new(SCAFFOLD).path();
+        let routines = compress(&path).unwrap();
+        assert_eq!(expand(&routines), join(&path));
+        assert!(routines.functions.len() <= 3);
+        assert!(routines.main.len() <= LIMIT);
+        assert!(routines.functions.iter().all(|f| f.len() <= LIMIT));
+        assert_eq!(
+            routines.input(false),
+            format!(
+                "{}\n{}\n{}\n{}\nn\n",
+                routines.main, routines.functions[0], routines.functions[1], routines.functions[2]
+            )
+        );
+
+        assert_eq!(compress(&["a", "b", "a"]).unwrap().main, "A,B,A");
+        // no two of these items fit together in a function
+        let long = ["aaaaaaaaaaa", "bbbbbbbbbbb", "ccccccccccc", "ddddddddddd"];
+        assert_eq!(compress(&long), None);
+    }
+
+    #[test]
+    fn test_capture_and_deliver() {
+        let mut program = Intcode::new("104,35,104,94,104,10,99".to_string());
+        let camera = Camera::capture(&mut program);
+        assert_eq!(camera.robot(), Some((Point::new(1, 0), Direction::Up)));
+        // only runs once woken up, when the first instruction becomes a multiplication
+        let program = Intcode::new("1,0,0,0,3,100,104,1234,99".to_string());
+        let routines = compress(&["R,2"]).unwrap();
+        assert_eq!(deliver(program, &routines, false).value, Some(1234));
+    }
+}
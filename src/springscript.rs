@@ -0,0 +1,533 @@
+//! Springscript of Advent of Code 2019 Day 21 (https://adventofcode.com/2019/day/21)
+//!
+//! A springscript decides whether the springdroid jumps, from the ground sensors `A` to `D`
+//! (walking) or `A` to `I` (running) telling if there is ground 1 to 9 tiles ahead. It writes
+//! the temporary register `T` and the jump register `J`, both false when the script starts.
+//! A jump lands 4 tiles ahead.
+
+use crate::ascii;
+use crate::intcode::Intcode;
+use log::*;
+use std::fmt;
+use std::str::FromStr;
+
+/// Maximum number of instructions of a script
+pub const MAX_INSTRUCTIONS: usize = 15;
+
+/// Distance of the tile where a jump lands
+const JUMP: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    Walk,
+    Run,
+}
+
+impl Mode {
+    /// Number of ground sensors readable in this mode
+    pub fn sensors(self) -> usize {
+        match self {
+            Mode::Walk => 4,
+            Mode::Run => 9,
+        }
+    }
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "walk" => Ok(Mode::Walk),
+            "run" => Ok(Mode::Run),
+            _ => Err(format!("Invalid mode: {} (expected walk or run)", s)),
+        }
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mode::Walk => write!(f, "WALK"),
+            Mode::Run => write!(f, "RUN"),
+        }
+    }
+}
+
+/// A register: ground sensor `Sensor(0)` being `A`, `T` or `J`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Register {
+    Sensor(usize),
+    T,
+    J,
+}
+
+impl FromStr for Register {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "T" => Ok(Register::T),
+            "J" => Ok(Register::J),
+            _ if s.len() == 1 && ("A"..="I").contains(&s) => {
+                Ok(Register::Sensor((s.as_bytes()[0] - b'A') as usize))
+            },
+            _ => Err(format!("unknown register {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Register::Sensor(i) => write!(f, "{}", (b'A' + *i as u8) as char),
+            Register::T => write!(f, "T"),
+            Register::J => write!(f, "J"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operation {
+    And,
+    Or,
+    Not,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Instruction {
+    pub operation: Operation,
+    pub source: Register,
+    pub target: Register,
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operation = match self.operation {
+            Operation::And => "AND",
+            Operation::Or => "OR",
+            Operation::Not => "NOT",
+        };
+        write!(f, "{} {} {}", operation, self.source, self.target)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Script {
+    instructions: Vec<Instruction>,
+}
+
+impl FromStr for Script {
+    type Err = String;
+
+    /// Parses one instruction per line, ignoring empty lines and a trailing `WALK` or `RUN`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut instructions = Vec::new();
+        for (n, line) in s.lines().enumerate() {
+            let error = |e: String| format!("line {}: {}", n + 1, e);
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            let operation = match words.first() {
+                None | Some(&"WALK") | Some(&"RUN") => continue,
+                Some(&"AND") => Operation::And,
+                Some(&"OR") => Operation::Or,
+                Some(&"NOT") => Operation::Not,
+                Some(w) => return Err(error(format!("unknown instruction {}", w))),
+            };
+            if words.len() != 3 {
+                return Err(error(format!("expected 2 registers in {:?}", line)));
+            }
+            let source = words[1].parse().map_err(error)?;
+            let target = words[2].parse().map_err(error)?;
+            instructions.push(Instruction {
+                operation,
+                source,
+                target,
+            });
+        }
+        Ok(Self { instructions })
+    }
+}
+
+impl fmt::Display for Script {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for instruction in &self.instructions {
+            writeln!(f, "{}", instruction)?;
+        }
+        Ok(())
+    }
+}
+
+impl Script {
+    pub fn new(instructions: Vec<Instruction>) -> Self {
+        Self { instructions }
+    }
+
+    pub fn len(&self) -> usize {
+        self.instructions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instructions.is_empty()
+    }
+
+    /// Checks that the script fits in the springdroid memory and only uses registers which
+    /// exist in `mode`
+    pub fn validate(&self, mode: Mode) -> Result<(), String> {
+        if self.len() > MAX_INSTRUCTIONS {
+            return Err(format!(
+                "{} instructions, at most {} fit in memory",
+                self.len(),
+                MAX_INSTRUCTIONS
+            ));
+        }
+        for (n, instruction) in self.instructions.iter().enumerate() {
+            if let Register::Sensor(i) = instruction.source {
+                if i >= mode.sensors() {
+                    return Err(format!(
+                        "line {}: register {} is not available in {} mode",
+                        n + 1,
+                        instruction.source,
+                        mode
+                    ));
+                }
+            }
+            if let Register::Sensor(_) = instruction.target {
+                return Err(format!(
+                    "line {}: register {} is read-only",
+                    n + 1,
+                    instruction.target
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns true if the droid jumps with the given readings of its sensors
+    pub fn jumps(&self, sensors: &[bool]) -> bool {
+        let mut t = false;
+        let mut j = false;
+        for instruction in &self.instructions {
+            let x = match instruction.source {
+                Register::Sensor(i) => sensors[i],
+                Register::T => t,
+                Register::J => j,
+            };
+            let y = match instruction.target {
+                Register::T => &mut t,
+                Register::J => &mut j,
+                Register::Sensor(_) => panic!("Invalid target: {}", instruction.target),
+            };
+            *y = match instruction.operation {
+                Operation::And => x && *y,
+                Operation::Or => x || *y,
+                Operation::Not => !x,
+            };
+        }
+        j
+    }
+
+    /// Returns the input to send to the springdroid
+    pub fn source(&self, mode: Mode) -> String {
+        format!("{}{}\n", self, mode)
+    }
+}
+
+/// Returns true if the droid crosses `hull` with this script, `#` being ground and `.` holes
+///
+/// The droid starts on the first tile, and everything past the end of the hull is ground.
+pub fn survives(script: &Script, mode: Mode, hull: &str) -> bool {
+    let hull = hull.chars().map(|c| c == '#').collect::<Vec<_>>();
+    let ground = |p: usize| hull.get(p).cloned().unwrap_or(true);
+    let mut position = 0;
+    while position < hull.len() {
+        if !ground(position) {
+            return false;
+        }
+        let sensors = (1..=mode.sensors())
+            .map(|d| ground(position + d))
+            .collect::<Vec<_>>();
+        position += if script.jumps(&sensors) { JUMP } else { 1 };
+    }
+    true
+}
+
+/// Returns the hull the droid fell in, from the animation shown by the springdroid
+pub fn scenario(fall: &str) -> Option<String> {
+    let lines = fall.lines().collect::<Vec<_>>();
+    let droid = lines.iter().position(|l| l.contains('@'))?;
+    let column = lines[droid].find('@')?;
+    lines[droid + 1..]
+        .iter()
+        .find(|l| l.contains('#') && l.chars().all(|c| c == '#' || c == '.'))
+        .and_then(|l| l.get(column..))
+        .map(|l| l.to_string())
+}
+
+/// Returns the script computing `J = !(P1 & P2 & ...) & Q1 & Q2 & ... & (R1 | R2 | ...)`,
+/// `roles` giving for each sensor whether it is unused (0) or belongs to P (1), Q (2) or R (3)
+fn candidate(roles: &[usize]) -> Script {
+    let sensors = |role: usize| {
+        roles
+            .iter()
+            .enumerate()
+            .filter(move |(_, r)| **r == role)
+            .map(|(i, _)| Register::Sensor(i))
+    };
+    let instruction = |operation, source, target| Instruction {
+        operation,
+        source,
+        target,
+    };
+    let mut instructions = Vec::new();
+    let mut p = sensors(1).peekable();
+    match p.next() {
+        Some(first) => {
+            instructions.push(instruction(Operation::Or, first, Register::J));
+            instructions.extend(p.map(|s| instruction(Operation::And, s, Register::J)));
+            instructions.push(instruction(Operation::Not, Register::J, Register::J));
+        },
+        None => instructions.push(instruction(Operation::Not, Register::T, Register::J)),
+    }
+    instructions.extend(sensors(2).map(|s| instruction(Operation::And, s, Register::J)));
+    let r = sensors(3).collect::<Vec<_>>();
+    if !r.is_empty() {
+        instructions.extend(
+            r.iter()
+                .map(|s| instruction(Operation::Or, *s, Register::T)),
+        );
+        instructions.push(instruction(Operation::And, Register::T, Register::J));
+    }
+    Script::new(instructions)
+}
+
+/// Calls `f` on every way to give `counts[role - 1]` of the sensors not in `roles` yet to each
+/// role, the others being unused, until it returns a script
+fn assign<F>(
+    roles: &mut Vec<usize>,
+    sensors: usize,
+    counts: [usize; 3],
+    f: &mut F,
+) -> Option<Script>
+where
+    F: FnMut(&[usize]) -> Option<Script>,
+{
+    if roles.len() == sensors {
+        return f(roles);
+    }
+    let left = sensors - roles.len() - 1;
+    for role in 0..4 {
+        let mut next = counts;
+        if role > 0 {
+            if next[role - 1] == 0 {
+                continue;
+            }
+            next[role - 1] -= 1;
+        }
+        if next.iter().sum::<usize>() > left {
+            continue;
+        }
+        roles.push(role);
+        let found = assign(roles, sensors, next, f);
+        roles.pop();
+        if found.is_some() {
+            return found;
+        }
+    }
+    None
+}
+
+/// Searches the shortest script letting the droid cross every hull of `scenarios`
+///
+/// The search is a brute force over the scripts jumping when there is a hole among some
+/// sensors, ground under some others, and ground under at least one of the remaining ones.
+/// Scripts are tried from the shortest, `candidate` writing `p + 1 + q + (r + 1)` instructions
+/// for `p`, `q` and `r` sensors in each role, the last term only when `r` is not 0.
+pub fn synthesize(mode: Mode, scenarios: &[String]) -> Option<Script> {
+    let sensors = mode.sensors();
+    let mut try_candidate = |roles: &[usize]| {
+        let script = candidate(roles);
+        if script.validate(mode).is_ok() && scenarios.iter().all(|s| survives(&script, mode, s)) {
+            Some(script)
+        } else {
+            None
+        }
+    };
+    for len in 1..=MAX_INSTRUCTIONS {
+        for p in 0..=sensors {
+            for q in 0..=sensors - p {
+                for r in 0..=sensors - p - q {
+                    if p + 1 + q + if r > 0 { r + 1 } else { 0 } != len {
+                        continue;
+                    }
+                    let found = assign(&mut Vec::new(), sensors, [p, q, r], &mut try_candidate);
+                    if found.is_some() {
+                        return found;
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+    /// The droid crossed the hull and reported the amount of hull damage
+    Damage(i64),
+    /// The droid fell: animation of the fall
+    Fall(String),
+}
+
+/// Validates `script` and runs it on the springdroid of `program`
+pub fn run(mut program: Intcode, script: &Script, mode: Mode) -> Result<Outcome, String> {
+    script.validate(mode)?;
+    let reply = ascii::send(&mut program, &script.source(mode));
+    debug!("{}", reply.text);
+    Ok(match reply.value {
+        Some(damage) => Outcome::Damage(damage),
+        None => Outcome::Fall(reply.text),
+    })
+}
+
+/// Synthesizes scripts until one makes the droid of `program` cross the hull, learning from
+/// every fall
+pub fn solve(program: &Intcode, mode: Mode) -> Result<(Script, i64), String> {
+    let mut scenarios = Vec::new();
+    loop {
+        let script = synthesize(mode, &scenarios)
+            .ok_or_else(|| format!("No script survives the {} scenarios", scenarios.len()))?;
+        debug!("trying script:\n{}", script);
+        match run(program.clone(), &script, mode)? {
+            Outcome::Damage(damage) => return Ok((script, damage)),
+            Outcome::Fall(fall) => {
+                let hull = scenario(&fall).ok_or("Unable to find the hull in the fall")?;
+                info!("the droid fell in {}", hull);
+                if scenarios.contains(&hull) {
+                    return Err(format!("The droid fell again in {}", hull));
+                }
+                scenarios.push(hull);
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WALK: &str = "NOT A J\nNOT B T\nOR T J\nNOT C T\nOR T J\nAND D J\n";
+
+    /// Prints a fall in `#.#` and reads the script, then halts
+    const FALL: &str = "104,64,104,10,104,35,104,46,104,35,104,10,3,100,99";
+
+    #[test]
+    fn test_parse() {
+        let script = WALK.parse::<Script>().unwrap();
+        assert_eq!(script.len(), 6);
+        assert_eq!(script.to_string(), WALK);
+        assert_eq!(script.source(Mode::Run), format!("{}RUN\n", WALK));
+        assert_eq!("OR E T\n\nWALK\n".parse::<Script>().unwrap().len(), 1);
+        assert_eq!(
+            "NOT A J\nXOR A J".parse::<Script>(),
+            Err("line 2: unknown instruction XOR".to_string())
+        );
+        assert_eq!(
+            "NOT K J".parse::<Script>(),
+            Err("line 1: unknown register K".to_string())
+        );
+        assert!("NOT A".parse::<Script>().is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(WALK.parse::<Script>().unwrap().validate(Mode::Walk), Ok(()));
+        let script = "OR E T".parse::<Script>().unwrap();
+        assert_eq!(
+            script.validate(Mode::Walk),
+            Err("line 1: register E is not available in WALK mode".to_string())
+        );
+        assert_eq!(script.validate(Mode::Run), Ok(()));
+        assert_eq!(
+            "NOT J A".parse::<Script>().unwrap().validate(Mode::Run),
+            Err("line 1: register A is read-only".to_string())
+        );
+        let long = "NOT A J\n".repeat(MAX_INSTRUCTIONS + 1);
+        assert!(
+            long.parse::<Script>()
+                .unwrap()
+                .validate(Mode::Walk)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_survives() {
+        let script = WALK.parse::<Script>().unwrap();
+        assert!(script.jumps(&[false, true, true, true]));
+        assert!(!script.jumps(&[true, true, true, true]));
+        assert!(survives(&script, Mode::Walk, "#####.###########"));
+        assert!(survives(&script, Mode::Walk, "#####..#.########"));
+        assert!(!survives(&Script::default(), Mode::Walk, "#####.###"));
+        // jumps too early to cross the second hole
+        assert!(!survives(&script, Mode::Walk, "#####.#..#.##.###"));
+    }
+
+    #[test]
+    fn test_scenario() {
+        let fall = "Didn't make it across:\n\n.................\n@................\n\
+                    #####.#..########\n\n.................\n.@...............\n\
+                    #####.#..########\n";
+        assert_eq!(scenario(fall), Some("#####.#..########".to_string()));
+        assert_eq!(scenario("no droid"), None);
+    }
+
+    #[test]
+    fn test_synthesize() {
+        let scenarios = vec![
+            "#####.###########".to_string(),
+            "#####..#.########".to_string(),
+            "#####...#########".to_string(),
+        ];
+        let script = synthesize(Mode::Walk, &scenarios).unwrap();
+        assert!(script.validate(Mode::Walk).is_ok());
+        assert!(scenarios.iter().all(|s| survives(&script, Mode::Walk, s)));
+        // as short as the best of every candidate
+        let shortest = (0..4usize.pow(4))
+            .map(|n| candidate(&(0..4).map(|i| n / 4usize.pow(i) % 4).collect::<Vec<_>>()))
+            .filter(|c| scenarios.iter().all(|s| survives(c, Mode::Walk, s)))
+            .map(|c| c.len())
+            .min();
+        assert_eq!(Some(script.len()), shortest);
+        // always jumping is enough when nothing was seen yet
+        assert_eq!(synthesize(Mode::Walk, &[]).unwrap().len(), 1);
+
+        // no script sensing up to 4 tiles ahead crosses both of them
+        let scenarios = vec![
+            "########.#.#...##".to_string(),
+            "######.##..#.####".to_string(),
+        ];
+        assert!(synthesize(Mode::Walk, &scenarios[..1]).is_some());
+        assert_eq!(synthesize(Mode::Walk, &scenarios), None);
+        let script = synthesize(Mode::Run, &scenarios).unwrap();
+        assert!(script.validate(Mode::Run).is_ok());
+        assert!(scenarios.iter().all(|s| survives(&script, Mode::Run, s)));
+    }
+
+    #[test]
+    fn test_run() {
+        let script = WALK.parse::<Script>().unwrap();
+        let program = Intcode::new(FALL.to_string());
+        match run(program, &script, Mode::Walk).unwrap() {
+            Outcome::Fall(fall) => assert_eq!(scenario(&fall), Some("#.#".to_string())),
+            o => panic!("unexpected outcome {:?}", o),
+        }
+        let program = Intcode::new("3,100,104,19355,99".to_string());
+        assert_eq!(
+            run(program, &script, Mode::Walk),
+            Ok(Outcome::Damage(19355))
+        );
+        let program = Intcode::new(FALL.to_string());
+        let script = "OR E J".parse::<Script>().unwrap();
+        assert!(run(program, &script, Mode::Walk).is_err());
+    }
+}
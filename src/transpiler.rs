@@ -0,0 +1,664 @@
+//! Ahead-of-time translation of an Intcode program into a Rust program
+//!
+//! Every instruction reachable from address 0 is translated once, in address order, right after
+//! the end of a labelled block named after its address. Those blocks are nested so that a `match`
+//! on the instruction pointer, with one arm per translated address, enters the code by breaking
+//! out of the block of the address. From there, the code runs straight to the next instruction
+//! and a jump with an immediate target further down breaks out of the block of its target.
+//! Only the jumps going back and the jumps to a computed address go through the `match` again.
+//!
+//! Before running an instruction, the generated code checks that the program did not patch it.
+//! If it did, or if the program jumps to an address which was not translated, the generated code
+//! hands over to an embedded interpreter.
+
+use crate::intcode::{Instruction, InstructionType, ParameterMode};
+use std::collections::BTreeMap;
+
+const TEMPLATE: &str = r#"// Generated by intcode2rs from an Intcode program, do not edit
+use std::io::{self, BufRead, BufWriter, Write};
+
+const PROGRAM: [i64; {size}] = [{program}];
+
+struct Machine {
+    mem: Vec<i64>,
+    rb: i64,
+    input: io::StdinLock<'static>,
+    output: BufWriter<io::Stdout>,
+}
+
+impl Machine {
+    fn read(&self, address: i64) -> i64 {
+        assert!(address >= 0, "Negative address {}", address);
+        self.mem.get(address as usize).cloned().unwrap_or(0)
+    }
+
+    fn write(&mut self, address: i64, value: i64) {
+        assert!(address >= 0, "Negative address {}", address);
+        let address = address as usize;
+        if address >= self.mem.len() {
+            self.mem.resize(address + 1, 0);
+        }
+        self.mem[address] = value;
+    }
+
+    fn input(&mut self) -> i64 {
+        self.output.flush().expect("Unable to write");
+        let mut input = String::new();
+        self.input
+            .read_line(&mut input)
+            .expect("Unable to read user input");
+        input
+            .trim_end()
+            .parse()
+            .expect("cannot parse integer from input")
+    }
+
+    fn output(&mut self, value: i64) {
+        writeln!(self.output, "{}", value).expect("Unable to write");
+    }
+
+    fn param(&self, pc: i64, n: u32) -> i64 {
+        let value = self.read(pc + n as i64);
+        match self.read(pc) / 10i64.pow(n + 1) % 10 {
+            1 => value,
+            2 => self.read(self.rb + value),
+            _ => self.read(value),
+        }
+    }
+
+    fn target(&self, pc: i64, n: u32) -> i64 {
+        let value = self.read(pc + n as i64);
+        match self.read(pc) / 10i64.pow(n + 1) % 10 {
+            2 => self.rb + value,
+            _ => value,
+        }
+    }
+
+    /// Runs the program from `pc` without relying on the translated code
+    fn interpret(&mut self, mut pc: i64) {
+        while pc >= 0 && (pc as usize) < self.mem.len() {
+            match self.read(pc) % 100 {
+                1 => {
+                    let value = self.param(pc, 1).wrapping_add(self.param(pc, 2));
+                    let target = self.target(pc, 3);
+                    self.write(target, value);
+                    pc += 4;
+                },
+                2 => {
+                    let value = self.param(pc, 1).wrapping_mul(self.param(pc, 2));
+                    let target = self.target(pc, 3);
+                    self.write(target, value);
+                    pc += 4;
+                },
+                3 => {
+                    let value = self.input();
+                    let target = self.target(pc, 1);
+                    self.write(target, value);
+                    pc += 2;
+                },
+                4 => {
+                    let value = self.param(pc, 1);
+                    self.output(value);
+                    pc += 2;
+                },
+                5 => pc = if self.param(pc, 1) != 0 { self.param(pc, 2) } else { pc + 3 },
+                6 => pc = if self.param(pc, 1) == 0 { self.param(pc, 2) } else { pc + 3 },
+                7 => {
+                    let value = (self.param(pc, 1) < self.param(pc, 2)) as i64;
+                    let target = self.target(pc, 3);
+                    self.write(target, value);
+                    pc += 4;
+                },
+                8 => {
+                    let value = (self.param(pc, 1) == self.param(pc, 2)) as i64;
+                    let target = self.target(pc, 3);
+                    self.write(target, value);
+                    pc += 4;
+                },
+                9 => {
+                    self.rb += self.param(pc, 1);
+                    pc += 2;
+                },
+                99 => return,
+                _ => panic!("Unknown instruction"),
+            }
+        }
+    }
+
+    #[allow(unused_mut, unused_labels, unreachable_code)]
+    fn run(&mut self) {
+        let mut pc: i64 = 0;
+        'dispatch: loop {
+{blocks}            match pc {
+{arms}                _ => return self.interpret(pc),
+            }
+{code}        }
+    }
+}
+
+fn main() {
+    let mut machine = Machine {
+        mem: PROGRAM.to_vec(),
+        rb: 0,
+        input: io::stdin().lock(),
+        output: BufWriter::new(io::stdout()),
+    };
+    machine.run();
+    machine.output.flush().expect("Unable to write");
+}
+"#;
+
+/// Returns the instructions reachable from address 0, indexed by address
+///
+/// The control flow is followed through every jump whose target is an immediate value. Jumps
+/// whose condition is an immediate value only follow the branch that can be taken.
+pub fn reachable(program: &[i64]) -> BTreeMap<usize, Instruction> {
+    let mut found = BTreeMap::new();
+    let mut todo = vec![0];
+    while let Some(address) = todo.pop() {
+        if found.contains_key(&address) {
+            continue;
+        }
+        let instruction = match program.get(address).and_then(|c| Instruction::decode(*c)) {
+            Some(i) if address + i.size() <= program.len() => i,
+            _ => continue,
+        };
+        let next = address + instruction.size();
+        match instruction.instruction_type {
+            InstructionType::Stop => (),
+            InstructionType::JumpIfTrue | InstructionType::JumpIfFalse => {
+                let when = instruction.instruction_type == InstructionType::JumpIfTrue;
+                let condition = match instruction.parameter_mode[0] {
+                    ParameterMode::Immediate => Some(program[address + 1] != 0),
+                    _ => None,
+                };
+                if condition != Some(when) {
+                    todo.push(next);
+                }
+                let target = program[address + 2];
+                if condition != Some(!when)
+                    && instruction.parameter_mode[1] == ParameterMode::Immediate
+                    && target >= 0
+                {
+                    todo.push(target as usize);
+                }
+            },
+            _ => todo.push(next),
+        }
+        found.insert(address, instruction);
+    }
+    found
+}
+
+/// Returns the expression reading the parameter `n` of the instruction at `address`
+fn param(program: &[i64], address: usize, instruction: &Instruction, n: usize) -> String {
+    let value = program[address + 1 + n];
+    match instruction.parameter_mode[n] {
+        ParameterMode::Immediate => format!("{}i64", value),
+        ParameterMode::Position => format!("self.read({})", value),
+        ParameterMode::Relative => format!("self.read({})", relative(value)),
+    }
+}
+
+/// Returns the address written by the parameter `n` of the instruction at `address`
+fn target(program: &[i64], address: usize, instruction: &Instruction, n: usize) -> String {
+    let value = program[address + 1 + n];
+    match instruction.parameter_mode[n] {
+        ParameterMode::Relative => relative(value),
+        _ => format!("{}", value),
+    }
+}
+
+fn relative(value: i64) -> String {
+    if value < 0 {
+        format!("self.rb - {}", value.unsigned_abs())
+    } else {
+        format!("self.rb + {}", value)
+    }
+}
+
+/// Where the control goes once an instruction is done
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transfer {
+    /// Runs the instruction translated right after, without any jump
+    Fallthrough,
+    /// Breaks out of the block of the instruction at this address, translated further down
+    Forward(usize),
+    /// Goes back to the instruction at this address through the `match`
+    Backward(usize),
+    /// Goes to an address computed when running, through the `match`
+    Computed,
+    /// Hands over to the interpreter at an address which was not translated
+    Interpret(i64),
+}
+
+/// Returns the transfers of every reachable instruction, indexed by address
+///
+/// A jump gives the transfer taken when the condition holds, then the one taken otherwise, the
+/// stop instruction gives none and every other instruction gives the transfer to its successor.
+pub fn transfers(
+    program: &[i64],
+    instructions: &BTreeMap<usize, Instruction>,
+) -> BTreeMap<usize, Vec<Transfer>> {
+    let addresses: Vec<usize> = instructions.keys().cloned().collect();
+    let to = |index: usize, target: i64| {
+        let address = addresses[index];
+        if target < 0 || !instructions.contains_key(&(target as usize)) {
+            Transfer::Interpret(target)
+        } else if addresses.get(index + 1) == Some(&(target as usize)) {
+            Transfer::Fallthrough
+        } else if target as usize > address {
+            Transfer::Forward(target as usize)
+        } else {
+            Transfer::Backward(target as usize)
+        }
+    };
+    addresses
+        .iter()
+        .enumerate()
+        .map(|(index, address)| {
+            let instruction = &instructions[address];
+            let next = to(index, (address + instruction.size()) as i64);
+            let transfers = match instruction.instruction_type {
+                InstructionType::Stop => vec![],
+                InstructionType::JumpIfTrue | InstructionType::JumpIfFalse => {
+                    let taken = match instruction.parameter_mode[1] {
+                        ParameterMode::Immediate => to(index, program[address + 2]),
+                        _ => Transfer::Computed,
+                    };
+                    vec![taken, next]
+                },
+                _ => vec![next],
+            };
+            (*address, transfers)
+        })
+        .collect()
+}
+
+/// Returns the statements performing `transfer`, `target` being the computed address
+fn jump(transfer: Transfer, target: &str) -> String {
+    match transfer {
+        Transfer::Fallthrough => String::new(),
+        Transfer::Forward(address) => format!("break 'a{};", address),
+        Transfer::Backward(address) => format!("pc = {};\ncontinue 'dispatch;", address),
+        Transfer::Computed => format!("pc = {};\ncontinue 'dispatch;", target),
+        Transfer::Interpret(address) => format!("return self.interpret({});", address),
+    }
+}
+
+/// Returns the code running the instruction at `address`, then jumping as told by `transfers`
+fn translate(
+    program: &[i64],
+    address: usize,
+    instruction: &Instruction,
+    transfers: &[Transfer],
+) -> String {
+    let p = |n| param(program, address, instruction, n);
+    let t = |n| target(program, address, instruction, n);
+    let next = address + instruction.size();
+    let then = |n: usize| jump(transfers[n], "");
+    let body = match instruction.instruction_type {
+        InstructionType::Add | InstructionType::Mul => {
+            let operation = match instruction.instruction_type {
+                InstructionType::Add => "wrapping_add",
+                _ => "wrapping_mul",
+            };
+            format!(
+                "let a = {};\n\
+                 let b = {};\n\
+                 self.write({}, a.{}(b));\n\
+                 {}",
+                p(0),
+                p(1),
+                t(2),
+                operation,
+                then(0)
+            )
+        },
+        InstructionType::LessThan | InstructionType::Equals => {
+            let operator = match instruction.instruction_type {
+                InstructionType::LessThan => "<",
+                _ => "==",
+            };
+            format!(
+                "let a = {};\n\
+                 let b = {};\n\
+                 self.write({}, (a {} b) as i64);\n\
+                 {}",
+                p(0),
+                p(1),
+                t(2),
+                operator,
+                then(0)
+            )
+        },
+        InstructionType::Input => format!(
+            "let value = self.input();\n\
+             self.write({}, value);\n\
+             {}",
+            t(0),
+            then(0)
+        ),
+        InstructionType::Output => format!(
+            "let value = {};\n\
+             self.output(value);\n\
+             {}",
+            p(0),
+            then(0)
+        ),
+        InstructionType::JumpIfTrue | InstructionType::JumpIfFalse => {
+            let (operator, inverse) = match instruction.instruction_type {
+                InstructionType::JumpIfTrue => ("!=", "=="),
+                _ => ("==", "!="),
+            };
+            // the branch going to the instruction translated right after needs no statement
+            let (operator, taken, otherwise) = match transfers[0] {
+                Transfer::Fallthrough => (inverse, transfers[1], Transfer::Fallthrough),
+                taken => (operator, taken, transfers[1]),
+            };
+            let target = match taken {
+                Transfer::Computed => format!("let target = {};\n", p(1)),
+                _ => String::new(),
+            };
+            if taken == Transfer::Fallthrough {
+                String::new()
+            } else {
+                format!(
+                    "{}if {} {} 0 {{\n    \
+                     {}\n\
+                     }}\n\
+                     {}",
+                    target,
+                    p(0),
+                    operator,
+                    jump(taken, "target").replace('\n', "\n    "),
+                    jump(otherwise, "")
+                )
+            }
+        },
+        InstructionType::Adjustbase => format!("self.rb += {};\n{}", p(0), then(0)),
+        InstructionType::Stop => "return;".to_string(),
+    };
+    let words = program[address..next]
+        .iter()
+        .map(|w| w.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let indent = " ".repeat(12);
+    let body = body
+        .lines()
+        .filter(|l| !l.is_empty())
+        .map(|l| format!("{}{}\n", indent, l))
+        .collect::<String>();
+    format!(
+        "{indent}}}\n\
+         {indent}// {address}: {disassembly}\n\
+         {indent}if self.mem[{address}..{next}] != [{words}] {{\n\
+         {indent}    return self.interpret({address});\n\
+         {indent}}}\n\
+         {body}",
+        address = address,
+        disassembly = instruction.disassemble(&program[address + 1..next]),
+        next = next,
+        words = words,
+        indent = indent,
+        body = body,
+    )
+}
+
+/// Translates `program` into the source of a standalone Rust program
+///
+/// The generated program reads its inputs from stdin, one integer per line, and writes each
+/// output on its own line, like `Intcode::run`.
+pub fn transpile(program: &[i64]) -> String {
+    let instructions = reachable(program);
+    let transfers = transfers(program, &instructions);
+    let blocks = instructions
+        .keys()
+        .rev()
+        .map(|address| format!("            'a{}: {{\n", address))
+        .collect::<String>();
+    let arms = instructions
+        .keys()
+        .map(|address| format!("                {0} => break 'a{0},\n", address))
+        .collect::<String>();
+    let code = instructions
+        .iter()
+        .map(|(address, instruction)| {
+            translate(program, *address, instruction, &transfers[address])
+        })
+        .collect::<String>();
+    TEMPLATE
+        .replace("{size}", &program.len().to_string())
+        .replace(
+            "{program}",
+            &program
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+        )
+        .replace("{blocks}", &blocks)
+        .replace("{arms}", &arms)
+        .replace("{code}", &code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::Intcode;
+    use std::env;
+    use std::fs;
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+    use std::process::{self, Command, Stdio};
+
+    const LARGER_EXAMPLE: &str = "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,\
+                                  98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,\
+                                  1000,1,20,4,20,1105,1,46,98,99";
+
+    /// Outputs 2 when the input is not 0, 1 otherwise
+    const FORWARD_JUMP: &str = "3,11,1005,11,8,104,1,99,104,2,99,0";
+
+    /// Outputs every value from the input down to 0, jumping back after each output
+    const COUNTDOWN: &str = "3,12,1001,12,-1,12,4,12,1005,12,2,99,0";
+
+    /// Programs and inputs from the day 5 and day 9 tests
+    const PROGRAMS: [(&str, &str, &[&str]); 16] = [
+        ("equals", "3,9,8,9,10,9,4,9,99,-1,8", &["2", "8"]),
+        ("equals_immediate", "3,3,1108,-1,8,3,4,3,99", &["2", "8"]),
+        ("lessthan", "3,9,7,9,10,9,4,9,99,-1,8", &["2", "9"]),
+        ("lessthan_immediate", "3,3,1107,-1,8,3,4,3,99", &["2", "9"]),
+        (
+            "jump",
+            "3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9",
+            &["2", "0"],
+        ),
+        (
+            "jump_immediate",
+            "3,3,1105,-1,9,1101,0,0,12,4,12,99,1",
+            &["2", "0"],
+        ),
+        ("larger_example", LARGER_EXAMPLE, &["2", "8", "9"]),
+        ("others", "101,-1,7,7,4,7,1105,11,0,99", &[""]),
+        (
+            "quine",
+            "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99",
+            &[""],
+        ),
+        ("large_output", "104,1125899906842624,99", &[""]),
+        ("large_mul", "1102,34915192,34915192,7,4,7,99,0", &[""]),
+        ("relative", "109,-1,4,1,99", &[""]),
+        ("relative_base", "109,1,9,2,204,-6,99", &[""]),
+        ("forward_jump", FORWARD_JUMP, &["0", "5"]),
+        ("countdown", COUNTDOWN, &["1", "3"]),
+        ("computed_jump", "106,0,6,99,104,1,3", &[""]),
+    ];
+
+    fn memory(code: &str) -> Vec<i64> {
+        Intcode::new(code.to_string()).memory().to_vec()
+    }
+
+    /// Build directory of a single test, removed when dropped
+    struct BuildDir(PathBuf);
+
+    impl BuildDir {
+        fn new(test: &str) -> Self {
+            let dir = env::temp_dir().join(format!("advent-{}-{}", test, process::id()));
+            fs::create_dir_all(&dir).expect("Unable to create build directory");
+            Self(dir)
+        }
+
+        fn compile(&self, name: &str, code: &str) -> PathBuf {
+            let source = self.0.join(format!("{}.rs", name));
+            fs::write(&source, transpile(&memory(code))).expect("Unable to write source");
+            let binary = self.0.join(name);
+            let status = Command::new(env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string()))
+                .args(["--edition", "2018", "-o"])
+                .arg(&binary)
+                .arg(&source)
+                .status()
+                .expect("Unable to run rustc");
+            assert!(status.success(), "{} does not compile", source.display());
+            binary
+        }
+    }
+
+    impl Drop for BuildDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn run_native(binary: &Path, input: &str) -> String {
+        let mut child = Command::new(binary)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("Unable to run translated program");
+        // programs without input may exit before their input is sent
+        let _ = child
+            .stdin
+            .take()
+            .expect("No stdin")
+            .write_all(input.as_bytes());
+        let output = child.wait_with_output().expect("Translated program failed");
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).expect("Not UTF-8")
+    }
+
+    fn run_interpreted(code: &str, input: &str) -> String {
+        let mut program = Intcode::new(code.to_string());
+        let mut output = Vec::new();
+        program.run(input.as_bytes(), &mut output);
+        String::from_utf8(output).expect("Not UTF-8")
+    }
+
+    #[test]
+    fn test_reachable() {
+        let found = reachable(&memory("1105,1,7,104,1,99,0,104,2,99"));
+        assert_eq!(found.keys().cloned().collect::<Vec<_>>(), vec![0, 7, 9]);
+
+        // the condition of the jump is patched by the input, which the analysis cannot see
+        let found = reachable(&memory("3,3,1105,-1,9,1101,0,0,12,4,12,99,1"));
+        assert_eq!(found.keys().cloned().collect::<Vec<_>>(), vec![0, 2, 9, 11]);
+
+        // the stop instruction is written by the program itself
+        let found = reachable(&memory("1101,100,-1,4,0"));
+        assert_eq!(found.keys().cloned().collect::<Vec<_>>(), vec![0]);
+    }
+
+    #[test]
+    fn test_transfers() {
+        use Transfer::*;
+        let transfers_of = |code| {
+            let program = memory(code);
+            transfers(&program, &reachable(&program))
+        };
+
+        let found = transfers_of(FORWARD_JUMP);
+        let expected = vec![
+            (0, vec![Fallthrough]),
+            (2, vec![Forward(8), Fallthrough]),
+            (5, vec![Fallthrough]),
+            (7, vec![]),
+            (8, vec![Fallthrough]),
+            (10, vec![]),
+        ];
+        assert_eq!(found, expected.into_iter().collect());
+
+        // the jump always goes to 7, the instruction at 3 is never translated
+        let found = transfers_of("1105,1,7,104,1,99,0,104,2,99");
+        let expected = vec![
+            (0, vec![Fallthrough, Interpret(3)]),
+            (7, vec![Fallthrough]),
+            (9, vec![]),
+        ];
+        assert_eq!(found, expected.into_iter().collect());
+
+        let found = transfers_of(COUNTDOWN);
+        let expected = vec![
+            (0, vec![Fallthrough]),
+            (2, vec![Fallthrough]),
+            (6, vec![Fallthrough]),
+            (8, vec![Backward(2), Fallthrough]),
+            (11, vec![]),
+        ];
+        assert_eq!(found, expected.into_iter().collect());
+
+        // the target of the jump is read from memory
+        let found = transfers_of("106,0,6,99,104,1,3");
+        assert_eq!(
+            found,
+            vec![(0, vec![Computed, Interpret(3)])]
+                .into_iter()
+                .collect()
+        );
+
+        // a jump to the following instruction does not jump at all
+        let found = transfers_of("1105,1,3,99");
+        assert_eq!(
+            found,
+            vec![(0, vec![Fallthrough, Fallthrough]), (3, vec![])]
+                .into_iter()
+                .collect()
+        );
+    }
+
+    #[test]
+    fn test_same_output_as_interpreter() {
+        let dir = BuildDir::new("same-output");
+        for (name, code, inputs) in PROGRAMS.iter() {
+            let binary = dir.compile(name, code);
+            for input in inputs.iter() {
+                let input = format!("{}\n", input);
+                assert_eq!(
+                    run_native(&binary, &input),
+                    run_interpreted(code, &input),
+                    "{} with input {:?}",
+                    name,
+                    input
+                );
+            }
+        }
+        let binary = dir.compile("larger_example", LARGER_EXAMPLE);
+        assert_eq!(run_native(&binary, "8\n"), "1000\n");
+        let binary = dir.compile("relative_base", "109,1,9,2,204,-6,99");
+        assert_eq!(run_native(&binary, ""), "204\n");
+        let binary = dir.compile("countdown", COUNTDOWN);
+        assert_eq!(run_native(&binary, "2\n"), "1\n0\n");
+    }
+
+    #[test]
+    fn test_self_modifying_code() {
+        let dir = BuildDir::new("self-modifying");
+        // the first instruction turns the output of 7 into an output of 8
+        let code = "1101,4,4,5,104,7,99";
+        assert_eq!(run_native(&dir.compile("patched", code), ""), "8\n");
+        assert_eq!(run_interpreted(code, ""), "8\n");
+
+        // the first instruction writes the stop instruction
+        let code = "1101,100,-1,4,0";
+        assert_eq!(run_native(&dir.compile("patched_stop", code), ""), "");
+    }
+}
@@ -0,0 +1,54 @@
+//! Helpers shared by several puzzles
+//!
+//!   - `Random` replays generated inputs from their seed
+//!   - `default_workers` sizes the pools of worker threads
+
+use std::thread;
+
+/// SplitMix64 pseudo-random generator, enough to replay a generated input from its seed
+#[derive(Clone, Debug)]
+pub struct Random(u64);
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        Random(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns true with the probability `p`
+    pub fn chance(&mut self, p: f64) -> bool {
+        ((self.next_u64() >> 11) as f64 / (1u64 << 53) as f64) < p
+    }
+}
+
+/// Number of workers matching the available cores
+pub fn default_workers() -> usize {
+    thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_random() {
+        // first outputs of SplitMix64 seeded with 0
+        let mut random = Random::new(0);
+        assert_eq!(random.next_u64(), 0xe220_a839_7b1d_cdaf);
+        assert_eq!(random.next_u64(), 0x6e78_9e6a_a1b9_65f4);
+
+        let mut a = Random::new(42);
+        let mut b = a.clone();
+        assert!((0..100).all(|_| a.next_u64() == b.next_u64()));
+        assert!((0..100).all(|_| !a.chance(0.0) && a.chance(1.0)));
+    }
+}